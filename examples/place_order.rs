@@ -1,6 +1,6 @@
 extern crate robinhood;
 
-use robinhood::Client;
+use robinhood::{Client, Price};
 
 use std::env;
 
@@ -16,11 +16,17 @@ fn main() {
 
     let instrument = rh.instrument_by_symbol("MSFT").unwrap();
     let mut market_order = rh.buy(30, instrument);
-    market_order.limit(0.010).opg();
+    market_order.limit("0.01".parse::<Price>().unwrap()).opg();
     //    pub fn sell(&self, quantity: u64, instrument: Instrument, account: Account) -> OrderBuilder {
 
     println!("{:?}", market_order);
-    market_order.send();
+    let preview = market_order.preview().unwrap();
+    println!("{:#?}", preview);
+    if !preview.has_buying_power() {
+        println!("Not enough buying power; not sending");
+        return;
+    }
+    println!("{:#?}", market_order.send());
     //println!("{:?}", market_order.url());
 
     //market_order.stop(7.54);
@@ -0,0 +1,25 @@
+extern crate robinhood;
+
+use robinhood::Client;
+
+use std::env;
+
+// Log in and list every watchlist along with what's on it
+fn main() {
+    let username = &env::var("RHUSER").unwrap();
+    let password = &env::var("RHPASSWORD").unwrap();
+
+    let rh = Client::new()
+        .login(username, password)
+        .build()
+        .unwrap();
+
+    for watchlist in rh.watchlists() {
+        let watchlist = watchlist.unwrap();
+        println!("Watchlist: {}", watchlist.name());
+        for item in watchlist.instruments(&rh) {
+            let instrument = rh.instrument_by_url(&item.unwrap().instrument()).unwrap();
+            println!("    {}", instrument.symbol());
+        }
+    }
+}
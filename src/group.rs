@@ -0,0 +1,733 @@
+//! Client-side bracket and OCO orders
+//!
+//! Robinhood has no linked orders, and it holds shares for the first sell order placed against
+//! them, so a second sell for the same shares (a take-profit next to a stop) is rejected. An
+//! `OrderGroup` works around both: only the stop rests at Robinhood, and the take-profit is
+//! watched from the quote. Once the quote reaches it, the stop is cancelled and, once that's
+//! confirmed, a limit order at the take-profit price is sent for whatever the stop didn't sell.
+//! If the quote falls back through the stop price before that limit fills, the limit is
+//! cancelled the same way and the stop is sent again for what's left.
+//!
+//! The stop goes in as soon as the entry fills anything. While the entry keeps filling, the
+//! stop is cancelled and sent again for the larger position.
+//!
+//! Nothing happens between calls to `update`, so call it on a timer; the take-profit is only
+//! noticed as often as it's called.
+//!
+//! A group is plain data. Save it after every `update` and load it again after a restart to
+//! pick up where it left off. Every leg is sent with a `ref_id` worked out from the group's id,
+//! so a leg sent just before a crash is found again rather than sent twice.
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use failure::Error;
+use serde_json;
+use uuid::Uuid;
+
+use super::{
+    _find_order_by_ref_id, _poll_order, Account, AccountData, Client, Instrument, InvalidOrder, Order,
+    OrderBuilder, OrderData, OrderState, Price, Quantity, Side,
+};
+
+/// Where a group is in its life
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GroupStatus {
+    /// Waiting for the entry order to finish. Whatever it's filled so far has a stop.
+    AwaitingEntry,
+    /// The stop is working and the take-profit is being watched
+    Open,
+    /// The take-profit price was reached: the stop is cancelled and a limit is working until
+    /// it fills or the price falls back through the stop
+    TakingProfit,
+    /// Everything was sold
+    Closed,
+    /// Ended without ever holding a position
+    Cancelled,
+    /// A leg ended without filling, leaving shares with no exit. Needs a human.
+    Broken(String),
+}
+
+impl GroupStatus {
+    pub fn is_done(&self) -> bool {
+        match *self {
+            GroupStatus::AwaitingEntry | GroupStatus::Open | GroupStatus::TakingProfit => false,
+            _ => true,
+        }
+    }
+}
+
+/// What the exit legs trade, once it's known
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Exit {
+    side: Side,
+    instrument: String,
+    account: String,
+}
+
+/// An entry order and the two exits that bracket it
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::path::Path;
+/// use std::thread;
+/// use std::time::Duration;
+/// use robinhood::{Client, OrderGroup};
+/// let rh = Client::new().login("username", "password").build().unwrap();
+/// let aapl = rh.instrument_by_symbol("AAPL").unwrap();
+/// let state = Path::new("aapl-bracket.json");
+/// let mut group = OrderGroup::bracket(
+///     rh.buy(10, aapl).limit(100),
+///     "110".parse().unwrap(),
+///     "95".parse().unwrap(),
+/// ).unwrap();
+/// while !group.update(&rh).unwrap().is_done() {
+///     group.save(state).unwrap();
+///     thread::sleep(Duration::from_secs(5));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderGroup {
+    id: String,
+    created_at: DateTime<Utc>,
+    entry: Option<String>,
+    exit: Option<Exit>,
+    /// Shares entered, or held to begin with
+    position: Quantity,
+    /// Shares sold by stops that were since cancelled and sent again for a new size
+    exited: Quantity,
+    take_profit_price: Price,
+    stop_loss_price: Price,
+    /// Legs sent so far. The next one is sent with `leg_ref_id(id, legs)`.
+    legs: u32,
+    take_profit: Option<String>,
+    stop_loss: Option<String>,
+    status: GroupStatus,
+}
+
+impl OrderGroup {
+    fn new(entry: Option<String>, take_profit: Price, stop_loss: Price) -> OrderGroup {
+        OrderGroup {
+            id: Uuid::new_v4().to_string(),
+            created_at: Utc::now(),
+            entry: entry,
+            exit: None,
+            position: Quantity::default(),
+            exited: Quantity::default(),
+            take_profit_price: take_profit,
+            stop_loss_price: stop_loss,
+            legs: 0,
+            take_profit: None,
+            stop_loss: None,
+            status: GroupStatus::AwaitingEntry,
+        }
+    }
+
+    /// Sends `entry` and brackets whatever it fills with a stop and a take-profit
+    ///
+    /// The entry has to be for whole shares, since the legs are good-till-cancelled and
+    /// fractional orders can only be good for the day.
+    pub fn bracket(entry: &OrderBuilder, take_profit: Price, stop_loss: Price) -> Result<OrderGroup, Error> {
+        if entry.dollar_amount.is_some() {
+            return Err(InvalidOrder::new("a bracket entry can't be dollar-based").into());
+        }
+        _whole_shares(entry.quantity)?;
+        let order = entry.send()?;
+        Ok(OrderGroup::new(Some(order.url()), take_profit, stop_loss))
+    }
+
+    /// Places a stop to sell `position` shares already held in the default account, and
+    /// watches for the take-profit
+    pub fn oco(
+        client: &Client,
+        instrument: Instrument,
+        position: Quantity,
+        take_profit: Price,
+        stop_loss: Price,
+    ) -> Result<OrderGroup, Error> {
+        _whole_shares(position)?;
+        let account = client._default_account()?;
+        let mut group = OrderGroup::new(None, take_profit, stop_loss);
+        group.position = position;
+        group.exit = Some(Exit {
+            side: Side::Sell,
+            instrument: instrument.url(),
+            account: account.url(),
+        });
+        group._place_stop(client)?;
+        group.status = GroupStatus::Open;
+        Ok(group)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn status(&self) -> &GroupStatus {
+        &self.status
+    }
+
+    /// Shares the exit legs cover
+    pub fn position(&self) -> Quantity {
+        self.position - self.exited
+    }
+
+    /// URLs of the entry, take-profit, and stop orders, once they exist
+    pub fn orders(&self) -> (Option<String>, Option<String>, Option<String>) {
+        (
+            self.entry.to_owned(),
+            self.take_profit.to_owned(),
+            self.stop_loss.to_owned(),
+        )
+    }
+
+    /// Checks every order in the group once and moves it along
+    pub fn update(&mut self, client: &Client) -> Result<GroupStatus, Error> {
+        match self.status {
+            GroupStatus::AwaitingEntry => self._update_entry(client)?,
+            GroupStatus::Open | GroupStatus::TakingProfit => self._update_legs(client)?,
+            _ => {}
+        }
+        Ok(self.status.to_owned())
+    }
+
+    /// Cancels whatever is still working and gives up on the group
+    pub fn cancel(&mut self, client: &Client) -> Result<(), Error> {
+        let urls = vec![&self.entry, &self.take_profit, &self.stop_loss];
+        for url in urls.into_iter().filter_map(|url| url.as_ref()) {
+            let order = _fetch(client, url)?;
+            if !order.data.state.is_terminal() {
+                client._cancel(&order)?;
+            }
+        }
+        if !self.status.is_done() {
+            self.status = GroupStatus::Cancelled;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        // Write beside the old copy and swap, so a crash never leaves half a file
+        let partial = path.with_extension("partial");
+        serde_json::to_writer_pretty(File::create(&partial)?, self)?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<OrderGroup, Error> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    fn _update_entry(&mut self, client: &Client) -> Result<(), Error> {
+        let url = match self.entry {
+            Some(ref url) => url.to_owned(),
+            // An OCO group that lost its stop to a crash
+            None => {
+                self._place_stop(client)?;
+                self.status = GroupStatus::Open;
+                return Ok(());
+            }
+        };
+        let entry = _fetch(client, &url)?;
+        match entry_step(self.position, &entry.data) {
+            EntryStep::Wait => {}
+            EntryStep::Cover(filled) => {
+                if self.exit.is_none() {
+                    let side = match entry.data.side {
+                        Side::Buy => Side::Sell,
+                        Side::Sell => Side::Buy,
+                        Side::Unknown(ref side) => {
+                            let reason = format!("entry has an unknown side {}", side);
+                            return Err(InvalidOrder::new(&reason).into());
+                        }
+                    };
+                    self.exit = Some(Exit {
+                        side: side,
+                        instrument: entry.data.instrument.to_owned(),
+                        account: entry.data.account.to_owned(),
+                    });
+                }
+                self._resize_stop(client, filled)?;
+            }
+            EntryStep::Open => self.status = GroupStatus::Open,
+            EntryStep::Cancelled => self.status = GroupStatus::Cancelled,
+        }
+        Ok(())
+    }
+
+    /// Swaps the stop for one covering `position` shares, less whatever the old one sold
+    fn _resize_stop(&mut self, client: &Client, position: Quantity) -> Result<(), Error> {
+        if let Some(url) = self.stop_loss.to_owned() {
+            let stop_loss = _fetch(client, &url)?;
+            let retired = if stop_loss.data.state.is_terminal() {
+                stop_loss.data
+            } else {
+                _cancel_leg(client, &stop_loss)?
+            };
+            self.exited = self.exited + retired.cumulative_quantity;
+            self.stop_loss = None;
+        }
+        self.position = position;
+        self._place_stop(client)
+    }
+
+    fn _place_stop(&mut self, client: &Client) -> Result<(), Error> {
+        let (quantity, stop_loss_price) = (self.position - self.exited, self.stop_loss_price);
+        let stop_loss = self._place_leg(client, quantity, |order| {
+            order.stop(stop_loss_price).gtc();
+        })?;
+        self.stop_loss = Some(stop_loss.url());
+        Ok(())
+    }
+
+    /// Sends the next leg, or picks it up if it was sent before a crash lost track of it
+    fn _place_leg<F>(&mut self, client: &Client, quantity: Quantity, configure: F) -> Result<Order, Error>
+    where
+        F: FnOnce(&mut OrderBuilder),
+    {
+        let exit = match self.exit {
+            Some(ref exit) => exit.to_owned(),
+            None => return Err(InvalidOrder::new("order group has no position to exit").into()),
+        };
+        let instrument = client.instrument_by_url(&exit.instrument)?;
+        let account = Account::new(client._get_json::<AccountData>("account", &exit.account)?);
+        let mut order = match exit.side {
+            Side::Buy => client.buy_with_account(quantity, instrument, account),
+            Side::Sell => client.sell_with_account(quantity, instrument, account),
+            Side::Unknown(ref side) => {
+                return Err(InvalidOrder::new(&format!("exit has an unknown side {}", side)).into())
+            }
+        };
+        configure(&mut order);
+
+        let since = self.created_at;
+        let leg = _place_once(
+            &leg_ref_id(&self.id, self.legs),
+            |ref_id| _find_order_by_ref_id(&client.client, ref_id, since),
+            |ref_id| order.set_ref_id(ref_id).send(),
+        )?;
+        self.legs += 1;
+        Ok(leg)
+    }
+
+    fn _update_legs(&mut self, client: &Client) -> Result<(), Error> {
+        let stop_loss = match self.stop_loss {
+            Some(ref url) => _fetch(client, url)?,
+            None => return Err(InvalidOrder::new("order group is open without a stop").into()),
+        };
+        let take_profit = match self.take_profit {
+            Some(ref url) => Some(_fetch(client, url)?),
+            None => None,
+        };
+        // Open watches for the take-profit, TakingProfit for the price falling back to the stop
+        let working = match take_profit {
+            Some(ref leg) => !leg.data.state.is_terminal(),
+            None => !stop_loss.data.state.is_terminal(),
+        };
+        let triggered = working
+            && match self._market_price(client)? {
+                Some((ref side, price)) if take_profit.is_some() => {
+                    fell_back(side, price, self.stop_loss_price)
+                }
+                Some((ref side, price)) => reached(side, price, self.take_profit_price),
+                None => false,
+            };
+
+        let position = self.position - self.exited;
+        let next = match step(
+            position,
+            &stop_loss.data,
+            take_profit.as_ref().map(|order| &order.data),
+            triggered,
+        ) {
+            Step::CancelStop => {
+                let cancelled = _cancel_leg(client, &stop_loss)?;
+                step(position, &cancelled, None, true)
+            }
+            Step::CancelTakeProfit => match take_profit {
+                Some(ref leg) => {
+                    let cancelled = _cancel_leg(client, leg)?;
+                    step(position, &stop_loss.data, Some(&cancelled), true)
+                }
+                None => Step::Wait,
+            },
+            next => next,
+        };
+
+        match next {
+            Step::Wait | Step::CancelStop | Step::CancelTakeProfit => {}
+            Step::Close => self.status = GroupStatus::Closed,
+            Step::TakeProfit(quantity) => {
+                let take_profit_price = self.take_profit_price;
+                let take_profit = self._place_leg(client, quantity, |order| {
+                    order.limit(take_profit_price).gtc();
+                })?;
+                self.take_profit = Some(take_profit.url());
+                self.status = GroupStatus::TakingProfit;
+            }
+            Step::RestoreStop(quantity) => {
+                // Everything but `quantity` was sold by the legs being retired
+                self.exited = self.position - quantity;
+                self.take_profit = None;
+                self._place_stop(client)?;
+                self.status = GroupStatus::Open;
+            }
+            Step::Broken(reason) => self.status = GroupStatus::Broken(reason),
+        }
+        Ok(())
+    }
+
+    /// The exit side and the price it would trade at now
+    fn _market_price(&self, client: &Client) -> Result<Option<(Side, Price)>, Error> {
+        let exit = match self.exit {
+            Some(ref exit) => exit,
+            None => return Ok(None),
+        };
+        let instrument = client.instrument_by_url(&exit.instrument)?;
+        let quote = client.quote_by_instrument(&instrument)?;
+        Ok(Some((exit.side.to_owned(), quote.reference_price(&exit.side))))
+    }
+}
+
+/// The ref_id of a group's `leg`th order. The same group and leg always give the same id.
+fn leg_ref_id(group_id: &str, leg: u32) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("{}:{}", group_id, leg).as_bytes()).to_string()
+}
+
+/// Sends an order with `ref_id` unless `lookup` finds one already sent
+fn _place_once<L, S>(ref_id: &str, lookup: L, send: S) -> Result<Order, Error>
+where
+    L: FnOnce(&str) -> Result<Option<Order>, Error>,
+    S: FnOnce(&str) -> Result<Order, Error>,
+{
+    match lookup(ref_id)? {
+        Some(order) => Ok(order),
+        None => send(ref_id),
+    }
+}
+
+/// Whether an exit on `side` could take profit at `target` with the market at `price`
+fn reached(side: &Side, price: Price, target: Price) -> bool {
+    match *side {
+        Side::Sell => price >= target,
+        Side::Buy => !price.is_zero() && price <= target,
+        Side::Unknown(_) => false,
+    }
+}
+
+/// Whether an exit on `side` would hit its stop at `stop` with the market at `price`
+fn fell_back(side: &Side, price: Price, stop: Price) -> bool {
+    match *side {
+        Side::Sell => !price.is_zero() && price <= stop,
+        Side::Buy => price >= stop,
+        Side::Unknown(_) => false,
+    }
+}
+
+/// What to do about a group's entry
+#[derive(Debug, Clone, PartialEq)]
+enum EntryStep {
+    Wait,
+    /// The entry filled more; put a stop on everything it's filled
+    Cover(Quantity),
+    /// The entry is done and everything it filled has a stop
+    Open,
+    /// The entry ended without filling anything
+    Cancelled,
+}
+
+fn entry_step(position: Quantity, entry: &OrderData) -> EntryStep {
+    if entry.cumulative_quantity > position {
+        EntryStep::Cover(entry.cumulative_quantity)
+    } else if !entry.state.is_terminal() {
+        EntryStep::Wait
+    } else if position.is_zero() {
+        EntryStep::Cancelled
+    } else {
+        EntryStep::Open
+    }
+}
+
+/// What to do about an open group's legs
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Wait,
+    /// Everything is sold
+    Close,
+    /// The take-profit was reached; cancel the stop
+    CancelStop,
+    /// The stop is cancelled; send the take-profit for what it didn't sell
+    TakeProfit(Quantity),
+    /// The price fell back through the stop; cancel the take-profit
+    CancelTakeProfit,
+    /// The take-profit is cancelled; send the stop again for what's left
+    RestoreStop(Quantity),
+    Broken(String),
+}
+
+fn step(position: Quantity, stop_loss: &OrderData, take_profit: Option<&OrderData>, triggered: bool) -> Step {
+    let take_profit_filled = take_profit.map_or(Quantity::default(), |leg| leg.cumulative_quantity);
+    if stop_loss.cumulative_quantity + take_profit_filled >= position
+        || stop_loss.state == OrderState::Filled
+        || take_profit.map_or(false, |leg| leg.state == OrderState::Filled)
+    {
+        return Step::Close;
+    }
+
+    if let Some(take_profit) = take_profit {
+        let sold = stop_loss.cumulative_quantity + take_profit.cumulative_quantity;
+        return match take_profit.state {
+            // Only the group cancels its take-profit, right before sending the stop again
+            OrderState::Cancelled => Step::RestoreStop(position - sold),
+            ref state if state.is_terminal() => Step::Broken(format!(
+                "take-profit {} ended {} without filling",
+                take_profit.id, state
+            )),
+            _ if triggered => Step::CancelTakeProfit,
+            _ => Step::Wait,
+        };
+    }
+
+    match stop_loss.state {
+        // Only the group cancels its stop, right before sending the take-profit. Finding it
+        // cancelled with no take-profit means a crash came in between.
+        OrderState::Cancelled => Step::TakeProfit(position - stop_loss.cumulative_quantity),
+        ref state if state.is_terminal() => Step::Broken(format!(
+            "stop {} ended {} without filling",
+            stop_loss.id, state
+        )),
+        _ if triggered => Step::CancelStop,
+        _ => Step::Wait,
+    }
+}
+
+/// The legs rest until cancelled, which fractional orders can't
+fn _whole_shares(quantity: Quantity) -> Result<(), Error> {
+    if !quantity.value().fract().is_zero() {
+        return Err(InvalidOrder::new(&format!(
+            "order groups need whole shares, not {}",
+            quantity
+        )).into());
+    }
+    Ok(())
+}
+
+fn _fetch(client: &Client, url: &str) -> Result<Order, Error> {
+    Ok(Order::new(client._get_json::<OrderData>("order", url)?))
+}
+
+/// Cancels a leg and waits until it's done, with whatever it filled in the meantime
+fn _cancel_leg(client: &Client, leg: &Order) -> Result<OrderData, Error> {
+    client._cancel(leg)?;
+    _poll_order(
+        &client.client,
+        &leg.data,
+        client.journal.as_ref(),
+        Duration::from_secs(30),
+        Duration::from_millis(250),
+        |order| order.state.is_terminal(),
+    )
+}
+
+#[cfg(test)]
+mod test_group {
+    use super::{
+        _place_once, entry_step, fell_back, leg_ref_id, reached, step, EntryStep, Exit, GroupStatus,
+        OrderGroup, Step,
+    };
+    use chrono::prelude::*;
+    use failure::err_msg;
+    use serde_json;
+    use std::cell::Cell;
+    use test_fixtures::{builder, order};
+    use {Price, Quantity, Side};
+
+    fn group() -> OrderGroup {
+        OrderGroup {
+            id: "b1946ac9-2d2e-4c5a-9a0e-5f0c7d2f1e11".to_string(),
+            created_at: Utc.ymd(2018, 6, 4).and_hms(14, 0, 0),
+            entry: Some("https://api.robinhood.com/orders/1/".to_string()),
+            exit: Some(Exit {
+                side: Side::Sell,
+                instrument: "https://api.robinhood.com/instruments/aapl/".to_string(),
+                account: "https://api.robinhood.com/accounts/1/".to_string(),
+            }),
+            position: Quantity::from(10),
+            exited: Quantity::default(),
+            take_profit_price: Price::from(110),
+            stop_loss_price: Price::from(95),
+            legs: 1,
+            take_profit: None,
+            stop_loss: Some("https://api.robinhood.com/orders/3/".to_string()),
+            status: GroupStatus::Open,
+        }
+    }
+
+    #[test]
+    fn persists() {
+        let group = group();
+        let json = serde_json::to_string(&group).unwrap();
+        assert_eq!(serde_json::from_str::<OrderGroup>(&json).unwrap(), group);
+    }
+
+    #[test]
+    fn ref_ids() {
+        let id = group().id;
+        assert_eq!(leg_ref_id(&id, 0), leg_ref_id(&id, 0));
+        assert!(leg_ref_id(&id, 0) != leg_ref_id(&id, 1));
+        assert!(leg_ref_id(&id, 0) != leg_ref_id("6f1c1c4e-4b8e-4a43-9d0b-3b7f7e2c9a10", 0));
+    }
+
+    #[test]
+    fn placed_once() {
+        // Sent before a crash: found, not sent again
+        let sent = Cell::new(0);
+        let leg = _place_once(
+            "ref-1",
+            |_| Ok(Some(order("3", "aapl", "sell", "confirmed", "0", "2018-06-04T14:00:00Z"))),
+            |_| {
+                sent.set(sent.get() + 1);
+                Err(err_msg("sent twice"))
+            },
+        ).unwrap();
+        assert_eq!(leg.data.id, "3");
+        assert_eq!(sent.get(), 0);
+
+        let leg = _place_once(
+            "ref-1",
+            |_| Ok(None),
+            |ref_id| {
+                assert_eq!(ref_id, "ref-1");
+                Ok(order("4", "aapl", "sell", "confirmed", "0", "2018-06-04T14:00:00Z"))
+            },
+        ).unwrap();
+        assert_eq!(leg.data.id, "4");
+
+        assert!(_place_once("ref-1", |_| Ok(None), |_| Err(err_msg("rejected"))).is_err());
+        assert!(_place_once("ref-1", |_| Err(err_msg("down")), |_| panic!("sent blind")).is_err());
+    }
+
+    #[test]
+    fn triggers() {
+        assert!(reached(&Side::Sell, Price::from(110), Price::from(110)));
+        assert!(!reached(&Side::Sell, Price::from(109), Price::from(110)));
+        assert!(reached(&Side::Buy, Price::from(90), Price::from(90)));
+        assert!(!reached(&Side::Buy, Price::from(91), Price::from(90)));
+        assert!(!reached(&Side::Buy, Price::default(), Price::from(90)));
+
+        assert!(fell_back(&Side::Sell, Price::from(95), Price::from(95)));
+        assert!(!fell_back(&Side::Sell, Price::from(96), Price::from(95)));
+        assert!(!fell_back(&Side::Sell, Price::default(), Price::from(95)));
+        assert!(fell_back(&Side::Buy, Price::from(105), Price::from(105)));
+        assert!(!fell_back(&Side::Buy, Price::from(104), Price::from(105)));
+    }
+
+    #[test]
+    fn entry_steps() {
+        let none = Quantity::default();
+        let working = order("1", "aapl", "buy", "confirmed", "0", "2018-06-04T14:00:00Z");
+        assert_eq!(entry_step(none, &working.data), EntryStep::Wait);
+
+        // The stop goes in on the first partial fill and grows with the entry
+        let partial = order("1", "aapl", "buy", "partially_filled", "4", "2018-06-04T14:00:00Z");
+        assert_eq!(entry_step(none, &partial.data), EntryStep::Cover(Quantity::from(4)));
+        assert_eq!(entry_step(Quantity::from(4), &partial.data), EntryStep::Wait);
+        let filled = order("1", "aapl", "buy", "filled", "10", "2018-06-04T14:00:00Z");
+        assert_eq!(entry_step(Quantity::from(4), &filled.data), EntryStep::Cover(Quantity::from(10)));
+        assert_eq!(entry_step(Quantity::from(10), &filled.data), EntryStep::Open);
+
+        let cancelled = order("1", "aapl", "buy", "cancelled", "4", "2018-06-04T14:00:00Z");
+        assert_eq!(entry_step(Quantity::from(4), &cancelled.data), EntryStep::Open);
+        let cancelled = order("1", "aapl", "buy", "cancelled", "0", "2018-06-04T14:00:00Z");
+        assert_eq!(entry_step(none, &cancelled.data), EntryStep::Cancelled);
+    }
+
+    #[test]
+    fn steps() {
+        let position = Quantity::from(10);
+        let stop = order("3", "aapl", "sell", "confirmed", "0", "2018-06-04T14:00:00Z");
+        assert_eq!(step(position, &stop.data, None, false), Step::Wait);
+        assert_eq!(step(position, &stop.data, None, true), Step::CancelStop);
+
+        let filled = order("3", "aapl", "sell", "filled", "10", "2018-06-04T14:00:00Z");
+        assert_eq!(step(position, &filled.data, None, true), Step::Close);
+
+        let rejected = order("3", "aapl", "sell", "rejected", "0", "2018-06-04T14:00:00Z");
+        match step(position, &rejected.data, None, false) {
+            Step::Broken(_) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn take_profit() {
+        let position = Quantity::from(10);
+        // Part of the stop filled before the cancel landed
+        let cancelled = order("3", "aapl", "sell", "cancelled", "4", "2018-06-04T14:00:00Z");
+        assert_eq!(
+            step(position, &cancelled.data, None, true),
+            Step::TakeProfit(Quantity::from(6))
+        );
+        // Picked up again after a crash between the cancel and the take-profit
+        assert_eq!(
+            step(position, &cancelled.data, None, false),
+            Step::TakeProfit(Quantity::from(6))
+        );
+
+        let working = order("4", "aapl", "sell", "confirmed", "0", "2018-06-04T14:01:00Z");
+        assert_eq!(step(position, &cancelled.data, Some(&working.data), false), Step::Wait);
+
+        let filled = order("4", "aapl", "sell", "filled", "6", "2018-06-04T14:01:00Z");
+        assert_eq!(step(position, &cancelled.data, Some(&filled.data), false), Step::Close);
+
+        let rejected = order("4", "aapl", "sell", "rejected", "0", "2018-06-04T14:01:00Z");
+        match step(position, &cancelled.data, Some(&rejected.data), false) {
+            Step::Broken(_) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn falls_back() {
+        let position = Quantity::from(10);
+        let stop = order("3", "aapl", "sell", "cancelled", "4", "2018-06-04T14:00:00Z");
+        // The price dropped back through the stop before the take-profit filled
+        let working = order("4", "aapl", "sell", "confirmed", "1", "2018-06-04T14:01:00Z");
+        assert_eq!(
+            step(position, &stop.data, Some(&working.data), true),
+            Step::CancelTakeProfit
+        );
+
+        let cancelled = order("4", "aapl", "sell", "cancelled", "1", "2018-06-04T14:01:00Z");
+        assert_eq!(
+            step(position, &stop.data, Some(&cancelled.data), true),
+            Step::RestoreStop(Quantity::from(5))
+        );
+        // Picked up again after a crash between the cancel and the new stop
+        assert_eq!(
+            step(position, &stop.data, Some(&cancelled.data), false),
+            Step::RestoreStop(Quantity::from(5))
+        );
+    }
+
+    #[test]
+    fn whole_shares() {
+        // Refused before the entry is sent
+        let entry = builder(Side::Buy, "1.5");
+        assert!(OrderGroup::bracket(&entry, Price::from(110), Price::from(95)).is_err());
+        let mut entry = builder(Side::Buy, "0");
+        entry.dollar_amount("250".parse().unwrap());
+        assert!(OrderGroup::bracket(&entry, Price::from(110), Price::from(95)).is_err());
+    }
+
+    #[test]
+    fn done() {
+        assert!(!GroupStatus::Open.is_done());
+        assert!(!GroupStatus::TakingProfit.is_done());
+        assert!(GroupStatus::Cancelled.is_done());
+        assert!(GroupStatus::Broken("stop rejected".to_string()).is_done());
+    }
+}
@@ -0,0 +1,321 @@
+//! Append-only order journal
+//!
+//! Every order the client tries to place, replace, or cancel is written to a file as one JSON
+//! object per line, along with what came back. Orders refused before sending and changes seen
+//! while waiting on an order are written too. Lines are only ever added, and each is synced to
+//! disk before the call that wrote it returns.
+//!
+//! A journal that can't be written stops trading: no order is placed or replaced unless the
+//! attempt was recorded first. Cancels still go out, since they only take risk off, and are
+//! recorded once they're answered. Once an order has gone out, its result is returned whether
+//! or not the outcome could be written; those write errors are kept for `Journal::take_errors`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::prelude::*;
+use failure::Error;
+use serde_json;
+
+use super::{Order, OrderData, OrderPayload};
+
+/// Something that happened to an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEvent {
+    /// Refused before sending, by validation, the day trade check, or the risk policy.
+    /// `payload` is missing when the order couldn't even be put together.
+    Refused {
+        ref_id: Option<String>,
+        payload: Option<OrderPayload>,
+        error: String,
+    },
+    /// About to post a new order, or a replacement for one
+    Sent {
+        ref_id: Option<String>,
+        payload: OrderPayload,
+    },
+    /// Robinhood took the order
+    Placed {
+        ref_id: Option<String>,
+        order: OrderData,
+    },
+    /// The order never made it, or we couldn't tell whether it did
+    Failed {
+        ref_id: Option<String>,
+        error: String,
+    },
+    Cancelled {
+        order_id: String,
+        error: Option<String>,
+    },
+    /// `order_id` was replaced by `order`
+    Replaced {
+        order_id: String,
+        order: Option<OrderData>,
+        error: Option<String>,
+    },
+    /// A later look at an order
+    Updated { order: OrderData },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: JournalEvent,
+}
+
+/// A JSON lines order journal
+///
+/// Clones write to the same file.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use robinhood::{Client, Journal};
+/// let journal = Journal::open("orders.jsonl").unwrap();
+/// let rh = Client::new()
+///     .login("username", "password")
+///     .journal(&journal)
+///     .build()
+///     .unwrap();
+///
+/// let entries = Journal::read("orders.jsonl").unwrap();
+/// for entry in Journal::history(&entries, "0c4dc9a8-9bd7-4d2e-8c2a-2c5a1c1f7a21") {
+///     println!("{}: {:?}", entry.at, entry.event);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Journal {
+    file: Arc<Mutex<File>>,
+    errors: Arc<Mutex<Vec<Error>>>,
+}
+
+impl Journal {
+    /// Opens a journal for appending, creating it if needed
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Journal, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Journal {
+            file: Arc::new(Mutex::new(file)),
+            errors: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    pub fn record(&self, event: JournalEvent) -> Result<(), Error> {
+        let entry = JournalEntry {
+            at: Utc::now(),
+            event: event,
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Errors writing entries that weren't allowed to fail the call that made them, oldest
+    /// first. Each is returned once.
+    pub fn take_errors(&self) -> Vec<Error> {
+        let mut errors = self.errors.lock().unwrap();
+        errors.drain(..).collect()
+    }
+
+    /// Records `event`, keeping any error for `take_errors` instead of returning it
+    fn _note(&self, event: JournalEvent) {
+        if let Err(err) = self.record(event) {
+            self.errors.lock().unwrap().push(err);
+        }
+    }
+
+    pub fn refused(&self, ref_id: &str, payload: Option<&OrderPayload>, error: &Error) {
+        self._note(JournalEvent::Refused {
+            ref_id: Some(ref_id.to_owned()),
+            payload: payload.map(|payload| payload.to_owned()),
+            error: error.to_string(),
+        })
+    }
+
+    pub fn sent(&self, payload: &OrderPayload) -> Result<(), Error> {
+        self.record(JournalEvent::Sent {
+            ref_id: payload.ref_id.to_owned(),
+            payload: payload.to_owned(),
+        })
+    }
+
+    /// Records how sending `payload` turned out
+    pub fn placed(&self, payload: &OrderPayload, result: &Result<Order, Error>) {
+        self._note(match *result {
+            Ok(ref order) => JournalEvent::Placed {
+                ref_id: payload.ref_id.to_owned(),
+                order: order.data.to_owned(),
+            },
+            Err(ref err) => JournalEvent::Failed {
+                ref_id: payload.ref_id.to_owned(),
+                error: err.to_string(),
+            },
+        })
+    }
+
+    pub fn cancelled(&self, order: &Order, result: &Result<(), Error>) {
+        self._note(JournalEvent::Cancelled {
+            order_id: order.data.id.to_owned(),
+            error: result.as_ref().err().map(|err| err.to_string()),
+        })
+    }
+
+    pub fn replaced(&self, order: &Order, result: &Result<Order, Error>) {
+        self._note(JournalEvent::Replaced {
+            order_id: order.data.id.to_owned(),
+            order: result.as_ref().ok().map(|order| order.data.to_owned()),
+            error: result.as_ref().err().map(|err| err.to_string()),
+        })
+    }
+
+    pub fn updated(&self, order: &Order) {
+        self._note(JournalEvent::Updated {
+            order: order.data.to_owned(),
+        })
+    }
+
+    /// Everything in a journal, oldest first
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<JournalEntry>, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push(serde_json::from_str(&line)?);
+        }
+        Ok(entries)
+    }
+
+    /// Every entry about one order, found by its ref_id or order id
+    ///
+    /// Replacements are followed, so the history runs from the first attempt through to the
+    /// last order that replaced it.
+    pub fn history(entries: &[JournalEntry], id: &str) -> Vec<JournalEntry> {
+        let mut ids = vec![id.to_owned()];
+        let mut history = Vec::new();
+        for entry in entries {
+            let (relevant, learned) = {
+                let known = |id: &String| ids.contains(id);
+                match entry.event {
+                    JournalEvent::Refused { ref ref_id, .. }
+                    | JournalEvent::Sent { ref ref_id, .. }
+                    | JournalEvent::Failed { ref ref_id, .. } => (ref_id.as_ref().map_or(false, &known), None),
+                    JournalEvent::Placed { ref ref_id, ref order } => {
+                        let relevant = ref_id.as_ref().map_or(false, &known) || known(&order.id);
+                        (relevant, Some(order.id.to_owned()))
+                    }
+                    JournalEvent::Cancelled { ref order_id, .. } => (known(order_id), None),
+                    JournalEvent::Replaced {
+                        ref order_id,
+                        ref order,
+                        ..
+                    } => (known(order_id), order.as_ref().map(|order| order.id.to_owned())),
+                    JournalEvent::Updated { ref order } => (known(&order.id), None),
+                }
+            };
+            if relevant {
+                if let Some(id) = learned {
+                    if !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                }
+                history.push(entry.to_owned());
+            }
+        }
+        history
+    }
+}
+
+#[cfg(test)]
+mod test_journal {
+    use super::{Journal, JournalEntry, JournalEvent};
+    use chrono::prelude::*;
+    use failure::err_msg;
+    use serde_json;
+    use std::fs::File;
+    use std::sync::{Arc, Mutex};
+    use test_fixtures::{order, payload};
+
+    fn entry(event: JournalEvent) -> JournalEntry {
+        JournalEntry {
+            at: Utc.ymd(2018, 6, 4).and_hms(14, 0, 0),
+            event: event,
+        }
+    }
+
+    #[test]
+    fn history() {
+        let mut sent = payload();
+        sent.ref_id = Some("ref-1".to_string());
+        let placed = order("1", "aapl", "buy", "confirmed", "0", "2018-06-04T14:00:00Z");
+        let replacement = order("2", "aapl", "buy", "confirmed", "0", "2018-06-04T14:01:00Z");
+        let filled = order("2", "aapl", "buy", "filled", "10", "2018-06-04T14:02:00Z");
+        let other = order("3", "msft", "buy", "confirmed", "0", "2018-06-04T14:00:00Z");
+
+        let entries = vec![
+            entry(JournalEvent::Refused {
+                ref_id: Some("ref-1".to_string()),
+                payload: None,
+                error: "quantity must be positive".to_string(),
+            }),
+            entry(JournalEvent::Sent {
+                ref_id: Some("ref-1".to_string()),
+                payload: sent,
+            }),
+            entry(JournalEvent::Placed {
+                ref_id: Some("ref-1".to_string()),
+                order: placed.data,
+            }),
+            entry(JournalEvent::Updated { order: other.data }),
+            entry(JournalEvent::Replaced {
+                order_id: "1".to_string(),
+                order: Some(replacement.data),
+                error: None,
+            }),
+            entry(JournalEvent::Updated { order: filled.data }),
+        ];
+
+        let history = Journal::history(&entries, "ref-1");
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[4], entries[5]);
+        assert_eq!(Journal::history(&entries, "3").len(), 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let entry = entry(JournalEvent::Cancelled {
+            order_id: "1".to_string(),
+            error: None,
+        });
+        let line = serde_json::to_string(&entry).unwrap();
+        assert!(line.starts_with(r#"{"at":"2018-06-04T14:00:00Z","event":"cancelled","#));
+        assert_eq!(serde_json::from_str::<JournalEntry>(&line).unwrap(), entry);
+    }
+
+    #[test]
+    fn write_errors() {
+        // Opened read-only, so every write fails
+        let file = File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+        let journal = Journal {
+            file: Arc::new(Mutex::new(file)),
+            errors: Arc::new(Mutex::new(Vec::new())),
+        };
+        assert!(journal.sent(&payload()).is_err());
+
+        let placed = order("1", "aapl", "buy", "confirmed", "0", "2018-06-04T14:00:00Z");
+        journal.placed(&payload(), &Ok(placed.clone()));
+        journal.updated(&placed);
+        journal.refused("ref-1", None, &err_msg("quantity must be positive"));
+        assert_eq!(journal.take_errors().len(), 3);
+        assert!(journal.take_errors().is_empty());
+    }
+}
@@ -30,11 +30,17 @@
 //!
 
 extern crate reqwest;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
 
 extern crate chrono;
+extern crate chrono_tz;
+
+extern crate rust_decimal;
+
+extern crate uuid;
 
 extern crate failure;
 
@@ -42,19 +48,46 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use reqwest::header::{Authorization, Bearer, ContentType, Headers, UserAgent};
-use reqwest::{Client as HTTPClient, Response};
+use reqwest::{Client as HTTPClient, Response, Url};
 
-use std::collections::HashMap;
+use chrono_tz::America::New_York;
+use chrono_tz::Tz;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use std::io::Read;
 
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use std::error::Error as StdError;
+use std::fmt;
+
 use chrono::naive::NaiveDate;
 use chrono::prelude::*;
 
 use failure::{Error, err_msg};
 
+use uuid::Uuid;
+
+pub use rust_decimal::Decimal;
+use rust_decimal::RoundingStrategy;
+use rust_decimal::prelude::ToPrimitive;
+
 #[macro_use]
 pub mod macros;
+pub mod group;
+pub mod journal;
+pub mod money;
+pub mod risk;
+pub mod watcher;
+
+pub use group::{GroupStatus, OrderGroup};
+pub use journal::{Journal, JournalEntry, JournalEvent};
+pub use money::{InvalidTick, Price, Quantity, TickRounding};
+pub use risk::{RiskPolicy, RiskViolation};
+pub use watcher::{OrderEvent, OrderWatcher};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PaginatedApiResponse<T> {
@@ -63,6 +96,207 @@ pub struct PaginatedApiResponse<T> {
     pub results: Vec<T>,
 }
 
+/// Returned when the API has no record of the requested resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotFound {
+    /// Kind of resource we were looking for (`instrument`, `order`, ...)
+    pub resource: String,
+    /// Symbol, id, or url used in the lookup
+    pub key: String,
+}
+
+impl NotFound {
+    pub fn new(resource: &str, key: &str) -> NotFound {
+        NotFound {
+            resource: resource.to_owned(),
+            key: key.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} not found: {}", self.resource, self.key)
+    }
+}
+
+impl StdError for NotFound {
+    fn description(&self) -> &str {
+        "resource not found"
+    }
+}
+
+/// Returned in strict mode when the API sends fields this crate doesn't know about
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownFields {
+    /// Model the fields turned up in (`OrderData`, `InstrumentData`, ...)
+    pub resource: String,
+    pub fields: Vec<String>,
+}
+
+impl fmt::Display for UnknownFields {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown {} fields: {}", self.resource, self.fields.join(", "))
+    }
+}
+
+impl StdError for UnknownFields {
+    fn description(&self) -> &str {
+        "unknown fields in API response"
+    }
+}
+
+/// A model that collects fields this crate doesn't know about in `extra`
+pub trait KnownFields {
+    /// Fails if the API sent anything that ended up in `extra`, here or in a model nested
+    /// inside. Handy in tests to catch the API growing new fields.
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields>;
+}
+
+impl<T: KnownFields> KnownFields for Vec<T> {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        for item in self {
+            item.ensure_known_fields()?;
+        }
+        Ok(())
+    }
+}
+
+fn _known_fields(
+    resource: &str,
+    extra: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), UnknownFields> {
+    if extra.is_empty() {
+        return Ok(());
+    }
+    Err(UnknownFields {
+        resource: resource.to_owned(),
+        fields: extra.keys().cloned().collect(),
+    })
+}
+
+#[cfg(test)]
+mod test_extra_fields {
+    use super::test_fixtures::{execution, order};
+    use super::{serde_json, KnownFields, OrderData, UnknownFields};
+
+    #[test]
+    fn captured() {
+        let order = order("1", "aapl", "buy", "filled", "10", "2018-06-04T14:00:00Z");
+        assert!(order.data.ensure_known_fields().is_ok());
+
+        let mut json = serde_json::to_value(&order.data).unwrap();
+        json["dollar_based_amount"] = serde_json::from_str(r#"{"amount": "25.00"}"#).unwrap();
+        let data = serde_json::from_value::<OrderData>(json).unwrap();
+        assert_eq!(data.extra["dollar_based_amount"]["amount"], "25.00");
+        assert_eq!(
+            data.ensure_known_fields(),
+            Err(UnknownFields {
+                resource: "OrderData".to_string(),
+                fields: vec!["dollar_based_amount".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let mut order = order("1", "aapl", "buy", "filled", "4", "2018-06-04T14:00:00Z");
+        let mut fill = serde_json::to_value(&execution("e1", "4", "9.90")).unwrap();
+        fill["fees"] = serde_json::Value::from("0.00");
+        order.data.executions.push(serde_json::from_value(fill).unwrap());
+        assert_eq!(
+            order.data.ensure_known_fields(),
+            Err(UnknownFields {
+                resource: "Execution".to_string(),
+                fields: vec!["fees".to_string()],
+            })
+        );
+    }
+}
+
+/// Returned when an order was sent but we couldn't find out whether Robinhood took it
+///
+/// Look it up later with `Client::order_by_ref_id` before sending it again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderUnconfirmed {
+    pub ref_id: String,
+    pub cause: String,
+}
+
+impl fmt::Display for OrderUnconfirmed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "order {} may or may not have been placed: {}", self.ref_id, self.cause)
+    }
+}
+
+impl StdError for OrderUnconfirmed {
+    fn description(&self) -> &str {
+        "order may or may not have been placed"
+    }
+}
+
+/// Returned when a replace cancelled the original order but never sent the replacement
+///
+/// Nothing for the order is working any more. Check what filled and send a new order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplaceAborted {
+    /// Id of the cancelled order
+    pub order_id: String,
+    pub cause: String,
+}
+
+impl fmt::Display for ReplaceAborted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "order {} was cancelled but its replacement was not sent: {}",
+            self.order_id, self.cause
+        )
+    }
+}
+
+impl StdError for ReplaceAborted {
+    fn description(&self) -> &str {
+        "order was cancelled but its replacement was not sent"
+    }
+}
+
+/// Returned when an order is refused locally, before it ever reaches Robinhood
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidOrder {
+    pub reason: String,
+}
+
+impl InvalidOrder {
+    pub fn new(reason: &str) -> InvalidOrder {
+        InvalidOrder {
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid order: {}", self.reason)
+    }
+}
+
+impl StdError for InvalidOrder {
+    fn description(&self) -> &str {
+        "invalid order"
+    }
+}
+
+fn _check_response(resource: &str, url: &str, res: &Response) -> Result<(), Error> {
+    if res.status().as_u16() == 404 {
+        return Err(NotFound::new(resource, url).into());
+    }
+    if !res.status().is_success() {
+        return Err(err_msg(format!("{} request failed: {}", resource, res.status())));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OAuthToken {
     backup_code: Option<String>,
@@ -96,6 +330,8 @@ pub struct Client {
     /// # Examples
     pub client: HTTPClient,
     authorized: bool,
+    risk: RiskPolicy,
+    journal: Option<Journal>,
 }
 
 impl Client {
@@ -138,6 +374,8 @@ impl Client {
             client_string: None,                 // OAuth2
             scope: Some("internal".to_string()), // OAuth2
             mfa_callback: cell,
+            risk: RiskPolicy::default(),
+            journal: None,
         }
     }
 
@@ -172,6 +410,21 @@ impl Client {
         req.send().unwrap()
     }
 
+    /// Grabs a single resource and decodes it. A 404 is reported as `NotFound`, and with the
+    /// `strict` feature, unknown fields as `UnknownFields`.
+    pub fn _get_json<T>(&self, resource: &str, url: &str) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned + KnownFields,
+    {
+        let mut res = self.client.get(url).send()?;
+        _check_response(resource, url, &res)?;
+        let data = res.json::<T>()?;
+        if cfg!(feature = "strict") {
+            data.ensure_known_fields()?;
+        }
+        Ok(data)
+    }
+
     pub fn _patch(&self, url: &str, patch: serde_json::Map<String, serde_json::Value>) -> String {
         let mut body = String::new();
         let mut res = self._patch_res(url, patch);
@@ -193,6 +446,11 @@ impl Client {
             .unwrap()
     }
 
+    fn _delete(&self, resource: &str, url: &str) -> Result<(), Error> {
+        let res = self.client.delete(url).send()?;
+        _check_response(resource, url, &res)
+    }
+
     /// Checks whether or not the client is authorized with an account.
     ///
     /// # Arguments
@@ -266,8 +524,143 @@ impl Client {
         Instruments::new_with_client(self.client.to_owned())
     }
 
+    /// Looks up a single instrument by its exact ticker symbol
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - Ticker symbol (`MSFT`, `AAPL`, ...)
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().build().unwrap();
+    /// let msft = rh.instrument_by_symbol("MSFT").unwrap();
+    /// println!("Instrument: {:#?}", msft);
+    /// ```
     pub fn instrument_by_symbol(&self, symbol: &str) -> Result<Instrument, Error> {
-        Instruments::search_by_symbol(symbol)
+        Instruments::search_by_symbol_with_client(self.client.to_owned(), symbol)
+    }
+
+    /// Creates a recursive iterator over instruments matching a free-text query
+    ///
+    /// Robinhood matches the query against symbols, names, and keywords.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Search terms (`tesla`, `oil etf`, ...)
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().build().unwrap();
+    /// for instrument in rh.search_instruments("microsoft").take(3) {
+    ///     println!("Instrument: {:#?}", instrument);
+    /// }
+    /// ```
+    pub fn search_instruments(&self, query: &str) -> Instruments {
+        let url = Url::parse_with_params(
+            "https://api.robinhood.com/instruments/",
+            &[("query", query)],
+        ).unwrap();
+        Instruments::new_with_client(self.client.to_owned())
+            .set_next(url.to_string())
+            .to_owned()
+    }
+
+    /// Grabs a single instrument by its id
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Robinhood's instrument id (a UUID)
+    pub fn instrument_by_id(&self, id: &str) -> Result<Instrument, Error> {
+        let url = format!("https://api.robinhood.com/instruments/{}/", id);
+        self.instrument_by_url(&url)
+    }
+
+    /// Grabs a single instrument by its url
+    ///
+    /// Orders, positions, and the like link to their instrument this way.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - Full instrument url
+    pub fn instrument_by_url(&self, url: &str) -> Result<Instrument, Error> {
+        let data = self._get_json::<InstrumentData>("instrument", url)?;
+        Ok(Instrument::new(data))
+    }
+
+    /// Grabs price history for a symbol
+    ///
+    /// Bars are as Robinhood reports them; run them through `SplitHistory::adjust_bars` to
+    /// smooth over splits.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - Ticker symbol
+    /// * `interval` - `5minute`, `10minute`, `hour`, `day`, or `week`
+    /// * `span` - `day`, `week`, `month`, `3month`, `year`, or `5year`
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().build().unwrap();
+    /// let historicals = rh.historicals("AAPL", "day", "5year").unwrap();
+    /// println!("{} bars", historicals.historicals.len());
+    /// ```
+    pub fn historicals(&self, symbol: &str, interval: &str, span: &str) -> Result<Historicals, Error> {
+        let url = Url::parse_with_params(
+            &format!("https://api.robinhood.com/quotes/historicals/{}/", symbol),
+            &[("interval", interval), ("span", span)],
+        )?;
+        self._get_json::<Historicals>("historicals", url.as_str())
+    }
+
+    /// Grabs the current quote for a symbol
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().build().unwrap();
+    /// let quote = rh.quote("MSFT").unwrap();
+    /// println!("Bid: {:?} Ask: {:?}", quote.bid_price, quote.ask_price);
+    /// ```
+    pub fn quote(&self, symbol: &str) -> Result<Quote, Error> {
+        let url = format!("https://api.robinhood.com/quotes/{}/", symbol);
+        self._get_json::<Quote>("quote", &url)
+    }
+
+    /// Grabs the current quote for an instrument
+    pub fn quote_by_instrument(&self, instrument: &Instrument) -> Result<Quote, Error> {
+        self._get_json::<Quote>("quote", &instrument.quote())
+    }
+
+    /// Grabs a market's trading hours for a day
+    ///
+    /// # Arguments
+    ///
+    /// * `market` - Market url, as found on an instrument
+    /// * `date` - Trading day
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// extern crate chrono;
+    /// extern crate robinhood;
+    ///
+    /// use robinhood::Client;
+    /// let rh = Client::new().build().unwrap();
+    /// let msft = rh.instrument_by_symbol("MSFT").unwrap();
+    /// let today = robinhood::market_date(chrono::Utc::now());
+    /// let hours = rh.market_hours(&msft.market(), today).unwrap();
+    /// println!("Open today: {}", hours.is_open);
+    /// ```
+    pub fn market_hours(&self, market: &str, date: NaiveDate) -> Result<MarketHours, Error> {
+        let url = format!("{}hours/{}/", market, date.format("%Y-%m-%d"));
+        self._get_json::<MarketHours>("market hours", &url)
     }
 
     pub fn accounts(&self) -> Accounts {
@@ -282,95 +675,514 @@ impl Client {
         //}
     }
 
-    pub fn sell(&self, quantity: u64, instrument: Instrument) -> OrderBuilder {
+    /// Creates a recursive iterator over the orders matching a query
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Filters to apply
+    pub fn query_orders(&self, query: &OrdersQuery) -> QueriedOrders {
+        QueriedOrders::new_with_client(self.client.to_owned(), query)
+    }
+
+    /// Creates a recursive iterator over the account's watchlists
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// for watchlist in rh.watchlists() {
+    ///     println!("Watchlist: {:#?}", watchlist);
+    /// }
+    /// ```
+    pub fn watchlists(&self) -> Watchlists {
+        Watchlists::new_with_client(self.client.to_owned())
+    }
+
+    /// Creates a new, empty watchlist
+    pub fn create_watchlist(&self, name: &str) -> Result<Watchlist, Error> {
+        let url = "https://api.robinhood.com/watchlists/";
+        let mut params = HashMap::new();
+        params.insert("name", name);
+        let mut res = self.client.post(url).form(&params).send()?;
+        _check_response("watchlist", url, &res)?;
+        Ok(Watchlist::new(res.json::<WatchlistData>()?))
+    }
+
+    /// Gives a watchlist a new name
+    pub fn rename_watchlist(&self, watchlist: &Watchlist, name: &str) -> Result<Watchlist, Error> {
+        let mut patch = serde_json::Map::new();
+        patch.insert("name".to_string(), serde_json::Value::String(name.to_owned()));
+        let url = watchlist.url();
+        let mut res = self._patch_res(&url, patch);
+        _check_response("watchlist", &url, &res)?;
+        Ok(Watchlist::new(res.json::<WatchlistData>()?))
+    }
+
+    /// Deletes a watchlist along with everything on it
+    pub fn delete_watchlist(&self, watchlist: &Watchlist) -> Result<(), Error> {
+        self._delete("watchlist", &watchlist.url())
+    }
+
+    /// Adds one or more symbols to a watchlist
+    ///
+    /// Symbols already on the list are left where they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `watchlist` - List to add to
+    /// * `symbols` - Ticker symbols
+    pub fn add_to_watchlist(
+        &self,
+        watchlist: &Watchlist,
+        symbols: &[&str],
+    ) -> Result<Vec<WatchlistItem>, Error> {
+        let url = format!("{}bulk_add/", watchlist.url());
+        let symbols = symbols.join(",");
+        let mut params = HashMap::new();
+        params.insert("symbols", symbols.as_str());
+        let mut res = self.client.post(url.as_str()).form(&params).send()?;
+        _check_response("watchlist", &url, &res)?;
+        let items = res.json::<Vec<WatchlistItemData>>()?;
+        Ok(items.into_iter().map(WatchlistItem::new).collect())
+    }
+
+    /// Takes a symbol off of a watchlist
+    pub fn remove_from_watchlist(&self, watchlist: &Watchlist, symbol: &str) -> Result<(), Error> {
+        let instrument = self.instrument_by_symbol(symbol)?;
+        let url = format!("{}{}/", watchlist.url(), instrument.id());
+        self._delete("watchlist item", &url)
+    }
+
+    /// Puts a watchlist's symbols in the given order
+    ///
+    /// # Arguments
+    ///
+    /// * `watchlist` - List to reorder
+    /// * `symbols` - Every symbol on the list, in the order they should be shown
+    pub fn reorder_watchlist(&self, watchlist: &Watchlist, symbols: &[&str]) -> Result<(), Error> {
+        let mut ids = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            ids.push(self.instrument_by_symbol(symbol)?.id());
+        }
+        let mut patch = serde_json::Map::new();
+        patch.insert("uuids".to_string(), serde_json::Value::String(ids.join(",")));
+        let url = format!("{}reorder/", watchlist.url());
+        let res = self._patch_res(&url, patch);
+        _check_response("watchlist", &url, &res)
+    }
+
+    pub fn sell<Q: Into<Quantity>>(&self, quantity: Q, instrument: Instrument) -> OrderBuilder {
         let account = self.accounts().nth(0).unwrap().unwrap();
         self.to_owned()
             .sell_with_account(quantity, instrument, account)
     }
 
-    pub fn sell_with_account(
+    pub fn sell_with_account<Q: Into<Quantity>>(
         &self,
-        quantity: u64,
+        quantity: Q,
         instrument: Instrument,
         account: Account,
     ) -> OrderBuilder {
-        OrderBuilder::new(
+        let mut order_builder = OrderBuilder::new(
             self.client.to_owned(),
-            "sell",
-            quantity,
+            Side::Sell,
+            quantity.into(),
             instrument,
             account,
-        )
-        // pub fn new( side: &str, quantity: u64, instrument: Instrument,
-        // account: Account ) -> OrderBuilder {
+        );
+        order_builder.risk = self.risk.to_owned();
+        order_builder.journal = self.journal.to_owned();
+        order_builder
     }
 
-    pub fn buy(&self, quantity: u64, instrument: Instrument) -> OrderBuilder {
+    pub fn buy<Q: Into<Quantity>>(&self, quantity: Q, instrument: Instrument) -> OrderBuilder {
         let account = self.accounts().nth(0).unwrap().unwrap();
         self.buy_with_account(quantity, instrument, account)
     }
 
-    pub fn buy_with_account(
+    pub fn buy_with_account<Q: Into<Quantity>>(
         &self,
-        quantity: u64,
+        quantity: Q,
         instrument: Instrument,
         account: Account,
     ) -> OrderBuilder {
-        let order_builder: OrderBuilder =
-            OrderBuilder::new(self.client.to_owned(), "buy", quantity, instrument, account);
+        let mut order_builder: OrderBuilder = OrderBuilder::new(
+            self.client.to_owned(),
+            Side::Buy,
+            quantity.into(),
+            instrument,
+            account,
+        );
+        order_builder.risk = self.risk.to_owned();
+        order_builder.journal = self.journal.to_owned();
         order_builder
-        // pub fn new( side: &str, quantity: u64, instrument: Instrument,
-        // account: Account ) -> OrderBuilder {
     }
 
-    pub fn cancel(&self, order: Order) -> bool {
-        if order.can_cancel().is_none() {
-            return false;
-        }
-        let res = self
-            .client
-            .post(order.can_cancel().unwrap().as_str())
-            .send()
-            .unwrap();
-        res.status().is_success()
+    /// Starts a market order for however many (fractional) shares `amount` dollars buys
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::{Client, Decimal};
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let spy = rh.instrument_by_symbol("SPY").unwrap();
+    /// let order = rh.buy_dollars(Decimal::from(25u64), spy).send();
+    /// ```
+    pub fn buy_dollars(&self, amount: Decimal, instrument: Instrument) -> OrderBuilder {
+        let account = self.accounts().nth(0).unwrap().unwrap();
+        self.buy_dollars_with_account(amount, instrument, account)
     }
 
-    pub fn positions(&self) -> Positions {
-        let account = self.accounts().nth(0).unwrap().unwrap();
-        Positions::new_with_client(self.client.to_owned())
-            .set_next(account.positions())
-            .to_owned()
+    pub fn buy_dollars_with_account(
+        &self,
+        amount: Decimal,
+        instrument: Instrument,
+        account: Account,
+    ) -> OrderBuilder {
+        let mut order_builder = self.buy_with_account(Quantity::default(), instrument, account);
+        order_builder.dollar_amount(amount);
+        order_builder
     }
 
-    pub fn positions_with_account(&self, account: Account) -> Positions {
-        Positions::new_with_client(self.client.to_owned())
-            .set_next(account.positions())
-            .to_owned()
+    pub fn cancel(&self, order: Order) -> bool {
+        self._cancel(&order).is_ok()
     }
 
-    pub fn positions_nonzero(&self) -> Positions {
-        Positions::new_with_client(self.client.to_owned())
-            .set_next("https://api.robinhood.com/positions/?nonzero=true".to_string())
-            .to_owned()
+    /// Cancels every open order matching `query`, carrying on past failures
+    ///
+    /// Only listing the orders can fail outright; what happened to each order is in the report.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::{Client, OrdersQuery, Side};
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let aapl = rh.instrument_by_symbol("AAPL").unwrap();
+    /// let report = rh
+    ///     .cancel_all(OrdersQuery::new().instrument(&aapl).side(Side::Buy))
+    ///     .unwrap();
+    /// for (order, error) in report.failed() {
+    ///     println!("Couldn't cancel {}: {}", order.id(), error);
+    /// }
+    /// ```
+    pub fn cancel_all(&self, query: &OrdersQuery) -> Result<CancelReport, Error> {
+        let orders = self
+            .query_orders(query.to_owned().open())
+            .collect::<Result<Vec<Order>, Error>>()?;
+        let mut report = CancelReport::default();
+        for order in orders {
+            let outcome = match self._cancel(&order) {
+                Ok(()) => CancelOutcome::Cancelled,
+                // It may have finished since we listed it
+                Err(err) => match order.refresh(self) {
+                    Ok(ref current) if current.data.state.is_terminal() => {
+                        CancelOutcome::AlreadyTerminal(current.data.state.to_owned())
+                    }
+                    _ => CancelOutcome::Failed(err),
+                },
+            };
+            report.results.push((order, outcome));
+        }
+        Ok(report)
     }
 
-    pub fn positions_nonzero_with_account(&self, account: Account) -> Positions {
-        let mut url: String = account.positions();
-        url.push_str("?nonzero=true");
-        Positions::new_with_client(self.client.to_owned())
-            .set_next(url)
-            .to_owned()
+    fn _cancel(&self, order: &Order) -> Result<(), Error> {
+        let result = self._post_cancel(order);
+        if let Some(ref journal) = self.journal {
+            journal.cancelled(order, &result);
+        }
+        result
     }
-}
 
-pub struct ClientBuilder {
-    username: Option<String>,
-    password: Option<String>,
+    fn _post_cancel(&self, order: &Order) -> Result<(), Error> {
+        let cancel_url = match order.data.can_cancel {
+            Some(ref url) => url.to_owned(),
+            None => {
+                return Err(InvalidOrder::new(&format!(
+                    "order {} can no longer be cancelled",
+                    order.data.id
+                )).into())
+            }
+        };
+        let res = self.client.post(cancel_url.as_str()).send()?;
+        _check_response("order", &cancel_url, &res)
+    }
+
+    fn _default_account(&self) -> Result<Account, Error> {
+        match self.accounts().next() {
+            Some(account) => account,
+            None => Err(NotFound::new("account", "default").into()),
+        }
+    }
+
+    /// Creates a recursive iterator over the portfolios of every account
+    pub fn portfolios(&self) -> Portfolios {
+        Portfolios::new_with_client(self.client.to_owned())
+    }
+
+    /// Grabs the portfolio of the default account
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let portfolio = rh.portfolio().unwrap();
+    /// println!("Equity: {}", portfolio.equity());
+    /// ```
+    pub fn portfolio(&self) -> Result<Portfolio, Error> {
+        self._default_account()?.portfolio(self)
+    }
+
+    /// Grabs the default account's equity history
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - `5minute`, `10minute`, `hour`, `day`, or `week`
+    /// * `span` - `day`, `week`, `month`, `3month`, `year`, `5year`, or `all`
+    pub fn portfolio_historicals(&self, interval: &str, span: &str) -> Result<PortfolioHistoricals, Error> {
+        self._default_account()?
+            .portfolio_historicals(self, interval, span)
+    }
+
+    /// Creates a recursive iterator over every dividend the account has been paid or is due
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// for dividend in rh.dividends().take(10) {
+    ///     println!("Dividend: {:#?}", dividend);
+    /// }
+    /// ```
+    pub fn dividends(&self) -> Dividends {
+        Dividends::new_with_client(self.client.to_owned())
+    }
+
+    /// Totals dividend income by symbol, month, and year
+    ///
+    /// Only dividends that have actually been paid (or reinvested) are counted.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let income = rh.dividend_income().unwrap();
+    /// for (year, amount) in &income.by_year {
+    ///     println!("{}: {:.2}", year, amount);
+    /// }
+    /// ```
+    pub fn dividend_income(&self) -> Result<DividendIncome, Error> {
+        let mut symbols: HashMap<String, String> = HashMap::new();
+        let mut income = DividendIncome::new();
+        for dividend in self.dividends() {
+            let dividend = dividend?;
+            if !dividend.is_paid() {
+                continue;
+            }
+            let instrument = dividend.instrument();
+            if !symbols.contains_key(&instrument) {
+                let symbol = self.instrument_by_url(&instrument)?.symbol();
+                symbols.insert(instrument.to_owned(), symbol);
+            }
+            income.add(&symbols[&instrument], &dividend);
+        }
+        Ok(income)
+    }
+
+    /// Changes the price or size of a working order
+    ///
+    /// Robinhood's replace endpoint is used when it's available, which keeps the order's
+    /// place in line. Otherwise the order is cancelled, the cancel is confirmed, and whatever
+    /// hasn't filled by then is sent again with the changes. If the order fills before the
+    /// cancel lands, nothing is resubmitted.
+    ///
+    /// The replacement goes through the client's `RiskPolicy` and the day trade check first,
+    /// like any order that's sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - Working order
+    /// * `changes` - New price, stop price, and/or total quantity
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::{Client, OrderChanges, Price};
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let order = rh.orders().nth(0).unwrap().unwrap();
+    /// let replaced = rh.replace(&order, OrderChanges::new().price("10.05".parse::<Price>().unwrap()));
+    /// ```
+    pub fn replace(&self, order: &Order, changes: &OrderChanges) -> Result<Order, Error> {
+        let result = self._replace(order, changes);
+        if let Some(ref journal) = self.journal {
+            journal.replaced(order, &result);
+        }
+        result
+    }
+
+    fn _replace(&self, order: &Order, changes: &OrderChanges) -> Result<Order, Error> {
+        let current = self._get_json::<OrderData>("order", &order.url())?;
+        if current.state.is_terminal() {
+            return Err(InvalidOrder::new(&format!(
+                "order {} is already {}",
+                current.id, current.state
+            )).into());
+        }
+
+        let instrument = self.instrument_by_url(&current.instrument)?;
+        let mut payload = OrderPayload::from_order(&current, &instrument.symbol());
+        changes.apply(&mut payload);
+        for price in payload.price.iter().chain(payload.stop_price.iter()) {
+            price.round_to_tick(price.tick_size(instrument.min_tick_size()), TickRounding::Reject)?;
+        }
+
+        // The replacement is a new order as far as the checks on sending go
+        let account = Account::new(self._get_json::<AccountData>("account", &current.account)?);
+        let mut replacement = match current.side {
+            Side::Buy => self.buy_with_account(payload.quantity, instrument, account),
+            Side::Sell => self.sell_with_account(payload.quantity, instrument, account),
+            Side::Unknown(ref side) => {
+                return Err(InvalidOrder::new(&format!(
+                    "order {} has an unknown side {}",
+                    current.id, side
+                )).into())
+            }
+        };
+        replacement._type = payload.type_field.to_owned();
+        replacement.allow_day_trade = changes.allow_day_trade;
+        // One ref_id covers both routes, since at most one of them places the replacement
+        payload.ref_id = Some(replacement.ref_id.to_owned());
+        let checked = replacement
+            ._check_day_trades()
+            .and_then(|_| self.risk.check(&replacement, &payload));
+        if let Err(err) = checked {
+            if let Some(ref journal) = self.journal {
+                journal.refused(&replacement.ref_id, Some(&payload), &err);
+            }
+            return Err(err);
+        }
+
+        if let Some(ref journal) = self.journal {
+            journal.sent(&payload)?;
+        }
+        let replace_url = format!("{}replace/", current.url);
+        match _post_order_to(&self.client, &replace_url, &payload) {
+            Err(ref err) if err.downcast_ref::<NotFound>().is_some() => {}
+            result => return result,
+        }
+
+        self._cancel(&Order::new(current.to_owned()))?;
+        // Past this point the original is going away, so failures have to say so
+        let cancelled = _poll_order(
+            &self.client,
+            &current,
+            self.journal.as_ref(),
+            Duration::from_secs(30),
+            Duration::from_millis(250),
+            |order| order.state.is_terminal(),
+        ).map_err(|err| ReplaceAborted {
+            order_id: current.id.to_owned(),
+            cause: err.to_string(),
+        })?;
+        if cancelled.state != OrderState::Cancelled {
+            return Err(InvalidOrder::new(&format!(
+                "order {} was {} before it could be replaced",
+                cancelled.id, cancelled.state
+            )).into());
+        }
+
+        payload.quantity = match _remaining_quantity(payload.quantity, cancelled.cumulative_quantity) {
+            Some(remaining) => remaining,
+            None => {
+                return Err(InvalidOrder::new(&format!(
+                    "order {} already filled {} shares; nothing left to replace",
+                    cancelled.id, cancelled.cumulative_quantity
+                )).into())
+            }
+        };
+        if let Some(ref journal) = self.journal {
+            journal.sent(&payload).map_err(|err| ReplaceAborted {
+                order_id: current.id.to_owned(),
+                cause: err.to_string(),
+            })?;
+        }
+        _post_order(&self.client, &payload)
+    }
+
+    /// Watches for changes to any order placed or updated from now on
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let subscription = rh.watch_orders().subscribe(|event| println!("{:?}", event));
+    /// // ...
+    /// subscription.stop().unwrap();
+    /// ```
+    pub fn watch_orders(&self) -> OrderWatcher {
+        let mut watcher = OrderWatcher::new_with_client(self.client.to_owned());
+        if let Some(ref journal) = self.journal {
+            watcher.journal(journal);
+        }
+        watcher
+    }
+
+    /// Finds an order by the `ref_id` it was sent with
+    ///
+    /// # Arguments
+    ///
+    /// * `ref_id` - Id generated by the `OrderBuilder`
+    /// * `since` - Only orders updated after this are searched
+    pub fn order_by_ref_id(&self, ref_id: &str, since: DateTime<Utc>) -> Result<Order, Error> {
+        match _find_order_by_ref_id(&self.client, ref_id, since)? {
+            Some(order) => Ok(order),
+            None => Err(NotFound::new("order", ref_id).into()),
+        }
+    }
+
+    pub fn positions(&self) -> Positions {
+        let account = self.accounts().nth(0).unwrap().unwrap();
+        Positions::new_with_client(self.client.to_owned())
+            .set_next(account.positions())
+            .to_owned()
+    }
+
+    pub fn positions_with_account(&self, account: Account) -> Positions {
+        Positions::new_with_client(self.client.to_owned())
+            .set_next(account.positions())
+            .to_owned()
+    }
+
+    pub fn positions_nonzero(&self) -> Positions {
+        Positions::new_with_client(self.client.to_owned())
+            .set_next("https://api.robinhood.com/positions/?nonzero=true".to_string())
+            .to_owned()
+    }
+
+    pub fn positions_nonzero_with_account(&self, account: Account) -> Positions {
+        let mut url: String = account.positions();
+        url.push_str("?nonzero=true");
+        Positions::new_with_client(self.client.to_owned())
+            .set_next(url)
+            .to_owned()
+    }
+}
+
+pub struct ClientBuilder {
+    username: Option<String>,
+    password: Option<String>,
     agent: String,
     client_string: Option<String>, // OAuth2
     scope: Option<String>,         /* OAuth2: read, watchlist, investments, trade, balances,
                                     * funding:all:read */
     mfa_callback: Rc<RefCell<FnMut(String) -> String>>,
+    risk: RiskPolicy,
+    journal: Option<Journal>,
 }
 
 impl ClientBuilder {
@@ -397,6 +1209,18 @@ impl ClientBuilder {
         self
     }
 
+    /// Limits every order sent through the client must pass
+    pub fn risk_policy(&mut self, policy: &RiskPolicy) -> &mut ClientBuilder {
+        self.risk = policy.to_owned();
+        self
+    }
+
+    /// Records every order the client places, replaces, or cancels
+    pub fn journal(&mut self, journal: &Journal) -> &mut ClientBuilder {
+        self.journal = Some(journal.to_owned());
+        self
+    }
+
     pub fn login(&mut self, username: &str, password: &str) -> &mut ClientBuilder {
         self.username = Some(username.to_owned());
         self.password = Some(password.to_owned());
@@ -508,6 +1332,8 @@ impl ClientBuilder {
         Ok(Client {
             client: client,
             authorized: authorized,
+            risk: self.risk.to_owned(),
+            journal: self.journal.to_owned(),
         })
     }
 }
@@ -531,11 +1357,12 @@ mod test_client_builder {
 
 iter_builder!(
     Instruments => Instrument as InstrumentData, "https://api.robinhood.com/instruments/" {
-    min_tick_size: Option<String> = None,
+    min_tick_size: Option<Price> = None,
     #[serde(rename = "type")]
     type_field: String = None,
-    splits: String = None,
-    margin_initial_ratio: String = None,
+    #[serde(rename = "splits")]
+    splits_url: String = None,
+    margin_initial_ratio: Decimal = None,
     url: String = None,
     quote: String = None,
     tradability: String = None,
@@ -546,40 +1373,79 @@ iter_builder!(
     fundamentals: String = None,
     state: String = None,
     country: String = None,
-    day_trade_ratio: String = None,
+    day_trade_ratio: Decimal = None,
     tradeable: bool = None,
-    maintenance_ratio: String = None,
+    maintenance_ratio: Decimal = None,
     id: String = None,
     market: String = None,
     simple_name: Option<String> = None,
     rhs_tradability: String = None,
+    fractional_tradability: Option<String> = None,
     tradable_chain_id: Option<String> = None
 });
 
+impl Instrument {
+    /// Creates a recursive iterator over this instrument's stock splits
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client used to make the request
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().build().unwrap();
+    /// let aapl = rh.instrument_by_symbol("AAPL").unwrap();
+    /// for split in aapl.splits(&rh) {
+    ///     println!("Split: {:#?}", split);
+    /// }
+    /// ```
+    pub fn splits(&self, client: &Client) -> Splits {
+        Splits::new_with_client(client.client.to_owned())
+            .set_next(self.splits_url())
+            .to_owned()
+    }
+
+    /// Gathers every split for this instrument so historical data can be adjusted
+    pub fn split_history(&self, client: &Client) -> Result<SplitHistory, Error> {
+        let splits = self.splits(client).collect::<Result<Vec<Split>, Error>>()?;
+        Ok(SplitHistory::new(splits))
+    }
+}
+
 impl Instruments {
     pub fn search_by_symbol<S>(symbol: S) -> Result<Instrument, Error>
     where
         S: Into<String>,
     {
-        let mut inst = Instruments {
-            results: vec![].into_iter(),
-            client: HTTPClient::new(),
-            next: Some(
-                format!(
-                    "https://api.robinhood.com/instruments/?symbol={}",
-                    symbol.into()
-                ).to_owned(),
-            ),
-        };
+        Instruments::search_by_symbol_with_client(HTTPClient::new(), symbol)
+    }
+
+    pub fn search_by_symbol_with_client<S>(client: HTTPClient, symbol: S) -> Result<Instrument, Error>
+    where
+        S: Into<String>,
+    {
+        let symbol = symbol.into();
+        let url = Url::parse_with_params(
+            "https://api.robinhood.com/instruments/",
+            &[("symbol", symbol.as_str())],
+        )?;
+        let mut inst = Instruments::new_with_client(client)
+            .set_next(url.to_string())
+            .to_owned();
 
-        inst.nth(0).unwrap()
+        match inst.next() {
+            Some(instrument) => instrument,
+            None => Err(NotFound::new("instrument", &symbol).into()),
+        }
     }
 }
 
 // Conditionally compile the module `test` only when the test-suite is run.
 #[cfg(test)]
 mod test_instruments {
-    use super::Client;
+    use super::{Client, NotFound};
 
     fn init_client() -> Client {
         Client::new().build().unwrap()
@@ -597,177 +1463,2358 @@ mod test_instruments {
             assert!(instrument.is_ok());
         }
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct MarginBalances {
-    day_trade_buying_power: String,
-    start_of_day_overnight_buying_power: String,
-    overnight_buying_power_held_for_orders: String,
-    cash_held_for_orders: String,
-    created_at: DateTime<Utc>,
-    unsettled_debit: String,
-    start_of_day_dtbp: String,
-    day_trade_buying_power_held_for_orders: String,
-    overnight_buying_power: String,
-    marked_pattern_day_trader_date: Option<NaiveDate>,
-    cash: String,
-    unallocated_margin_cash: String,
-    updated_at: DateTime<Utc>,
-    cash_available_for_withdrawal: String,
-    margin_limit: String,
-    outstanding_interest: String,
-    uncleared_deposits: String,
-    unsettled_funds: String,
-    gold_equity_requirement: String,
-    day_trade_ratio: String,
-    overnight_ratio: String,
-}
+    #[test]
+    fn search_instruments() {
+        let rh = init_client();
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct InstantEligibility {
-    updated_at: Option<DateTime<Utc>>,
-    reason: String,
-    reinstatement_date: Option<DateTime<Utc>>,
-    reversal: Option<serde_json::Value>,
-    state: String,
-}
+        let msft = rh.instrument_by_symbol("MSFT").unwrap();
+        assert_eq!(msft.symbol(), "MSFT");
 
-iter_builder!(
-    Accounts => Account as AccountData, "https://api.robinhood.com/accounts/" {
-    deactivated: bool = None,
-    updated_at: DateTime<Utc> = None,
-    margin_balances: MarginBalances = None,
-    portfolio: String = None,
-    cash_balances: serde_json::Value = None,
-    can_downgrade_to_cash: String = None,
-    withdrawal_halted: bool = None,
-    cash_available_for_withdrawal: String = None,
-    #[serde(rename = "type")]
-    type_field: String = None,
-    sma: String = None,
-    sweep_enabled: bool = None,
-    deposit_halted: bool = None,
-    buying_power: String = None,
-    user: String = None,
-    max_ach_early_access_amount: String = None,
-    instant_eligibility: InstantEligibility = None,
-    cash_held_for_orders: String = None,
-    only_position_closing_trades: bool = None,
-    url: String = None,
-    positions: String = None,
-    created_at: DateTime<Utc> = None,
-    cash: String = None,
-    sma_held_for_orders: String = None,
-    unsettled_debit: String = None,
-    account_number: String = None,
-    uncleared_deposits: String = None,
-    unsettled_funds: String = None,
-    nummus_enabled: Option<bool> = None, // Crypto
-    option_level: String = None,
-    is_pinnacle_account: bool = None
-});
+        let by_id = rh.instrument_by_id(&msft.id()).unwrap();
+        assert_eq!(by_id.url(), msft.url());
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Execution {
-    timestamp: String,
-    price: String,
-    settlement_date: String,
-    id: String,
-    quantity: String,
-}
+        let found = rh.search_instruments("microsoft")
+            .take(10)
+            .any(|i| i.map(|i| i.symbol() == "MSFT").unwrap_or(false));
+        assert!(found);
+    }
 
-iter_builder!(
-    Orders => Order as OrderData, "https://api.robinhood.com/orders/" {
-    account: String = None,
-    average_price: Option<String> = None,
-    #[serde(rename = "cancel")]
-    can_cancel: Option<String> = None,
-    created_at: DateTime<Utc> = None,
-    cumulative_quantity: String = None,
-    executions: Vec<Execution> = Vec::new(),
-    extended_hours: bool = None,
-    fees: String = None,
-    id: String = None,
-    instrument: String = None,
-    last_transaction_at: DateTime<Utc> = None,
-    override_day_trade_checks: bool = None,
-    override_dtbp_checks: bool = None,
-    position: String = None,
-    price: Option<String> = None,
-    quantity: String = None,
-    ref_id: Option<String> = None,
-    reject_reason: Option<String> = None,
-    response_category: Option<String> = None,
-    side: String = None,
-    state: String = None,
-    stop_price: Option<String> = None,
-    time_in_force: String = None,
-    trigger: String = None,
-    #[serde(rename = "type")]
-    type_field: String = None,
-    updated_at: DateTime<Utc> = None,
-    url: String = None
-});
+    #[test]
+    fn missing_instrument() {
+        let rh = init_client();
+
+        let err = rh.instrument_by_symbol("NOT-A-REAL-SYMBOL").unwrap_err();
+        assert_eq!(err.downcast::<NotFound>().unwrap(), NotFound::new("instrument", "NOT-A-REAL-SYMBOL"));
+
+        let err = rh.instrument_by_id("00000000-0000-0000-0000-000000000000").unwrap_err();
+        assert_eq!(err.downcast::<NotFound>().unwrap().resource, "instrument");
+    }
+}
 
 iter_builder!(
-    Positions => Position as PositionData, "https://api.robinhood.com/accounts/{account_id}/positions/" {
-    shares_held_for_stock_grants: String = None,
-    account: String = None,
-    intraday_quantity: String = None,
-    intraday_average_buy_price: String = None,
+    Splits => Split as SplitData, "https://api.robinhood.com/instruments/{instrument_id}/splits/" {
     url: String = None,
-    created_at: DateTime<Utc> = None,
-    updated_at: DateTime<Utc> = None,
-    shares_held_for_buys: String = None,
-    average_buy_price: String = None,
     instrument: String = None,
-    shares_held_for_sells: String = None,
-    quantity: String = None
+    execution_date: NaiveDate = None,
+    multiplier: Decimal = None,
+    divisor: Decimal = None
 });
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+impl Split {
+    /// Number of new shares issued for each old share (4 for a 4:1 split, 0.1 for a 1:10
+    /// reverse split)
+    pub fn ratio(&self) -> Result<Decimal, Error> {
+        if self.data.multiplier.is_zero() || self.data.divisor.is_zero() {
+            return Err(err_msg(format!("split {} has a zero ratio", self.data.url)));
+        }
+        Ok(self.data.multiplier / self.data.divisor)
     }
 }
 
+/// Every split an instrument has gone through, used to restate historical prices and
+/// quantities in today's share terms
 #[derive(Debug, Clone)]
-pub struct OrderBuilder {
-    client: HTTPClient,
-    time_in_force: String,
-    stop_price: Option<f64>,
-    instrument: Instrument,
-    override_dtbp_checks: bool,
-    _type: String,
-    price: Option<f64>,
-    extended_hours: bool,
-    account: Account,
-    side: String,
-    override_day_trade_checks: bool,
-    quantity: u64,
+pub struct SplitHistory {
+    splits: Vec<Split>,
 }
 
-impl OrderBuilder {
-    pub fn new(
-        ref mut client: HTTPClient,
-        side: &str,
-        quantity: u64,
-        instrument: Instrument,
-        account: Account,
-    ) -> OrderBuilder {
-        OrderBuilder {
-            client: client.to_owned(),
+impl SplitHistory {
+    pub fn new(splits: Vec<Split>) -> SplitHistory {
+        SplitHistory { splits: splits }
+    }
 
-            _type: "market".to_owned(),
-            side: side.to_owned(),
-            time_in_force: "gfd".to_owned(), //  `gfd`, `gtc`, or `opg`
+    pub fn splits(&self) -> &[Split] {
+        &self.splits
+    }
+
+    /// Combined ratio of every split executed after `date`
+    ///
+    /// Anything priced on or after a split's execution date is already in post-split terms.
+    pub fn factor_since(&self, date: NaiveDate) -> Result<Decimal, Error> {
+        let mut factor = Decimal::from(1u64);
+        for split in self.splits.iter().filter(|s| s.data.execution_date > date) {
+            factor = factor * split.ratio()?;
+        }
+        Ok(factor)
+    }
+
+    /// Restates a per-share price from `date` in today's share terms
+    pub fn adjust_price(&self, price: Price, date: NaiveDate) -> Result<Price, Error> {
+        Ok(Price(price.value() / self.factor_since(date)?))
+    }
+
+    /// Restates a share count from `date` in today's share terms
+    pub fn adjust_quantity(&self, quantity: Quantity, date: NaiveDate) -> Result<Quantity, Error> {
+        Ok(Quantity(quantity.value() * self.factor_since(date)?))
+    }
+
+    /// Restates an average buy price for shares acquired on `date`
+    ///
+    /// The total cost basis is untouched; only the per-share figure moves.
+    pub fn adjust_average_buy_price(&self, price: Price, date: NaiveDate) -> Result<Price, Error> {
+        self.adjust_price(price, date)
+    }
+
+    /// Restates an order execution's price and quantity in today's share terms
+    pub fn adjust_execution(&self, execution: &Execution) -> Result<Execution, Error> {
+        let date = market_date(execution.timestamp);
+
+        let mut adjusted = execution.to_owned();
+        adjusted.price = self.adjust_price(execution.price, date)?;
+        adjusted.quantity = self.adjust_quantity(execution.quantity, date)?;
+        Ok(adjusted)
+    }
+
+    /// Restates a series of historical bars so splits don't show up as price gaps
+    pub fn adjust_bars(&self, bars: &[HistoricalBar]) -> Result<Vec<HistoricalBar>, Error> {
+        let mut adjusted = Vec::with_capacity(bars.len());
+        for bar in bars {
+            let factor = self.factor_since(_bar_date(bar.begins_at))?;
+            let mut bar = bar.to_owned();
+            bar.open_price = Price(bar.open_price.value() / factor);
+            bar.close_price = Price(bar.close_price.value() / factor);
+            bar.high_price = Price(bar.high_price.value() / factor);
+            bar.low_price = Price(bar.low_price.value() / factor);
+            bar.volume = (Decimal::from(bar.volume) * factor)
+                .round()
+                .to_u64()
+                .ok_or_else(|| err_msg("split-adjusted volume is out of range"))?;
+            adjusted.push(bar);
+        }
+        Ok(adjusted)
+    }
+}
+
+/// The trading day a bar covers. Day and week bars are stamped with their date at midnight
+/// UTC; anything shorter starts at a real moment, on the market's clock.
+fn _bar_date(begins_at: DateTime<Utc>) -> NaiveDate {
+    if begins_at.time() == NaiveTime::from_hms(0, 0, 0) {
+        begins_at.naive_utc().date()
+    } else {
+        market_date(begins_at)
+    }
+}
+
+#[cfg(test)]
+mod test_splits {
+    use super::test_fixtures::execution;
+    use super::{Decimal, HistoricalBar, Price, Quantity, Split, SplitData, SplitHistory};
+    use chrono::prelude::*;
+    use chrono::naive::NaiveDate;
+    use std::str::FromStr;
+
+    fn split(date: &str, multiplier: &str, divisor: &str) -> Split {
+        let data: SplitData = super::serde_json::from_value(json_split(date, multiplier, divisor))
+            .unwrap();
+        Split::new(data)
+    }
+
+    fn json_split(date: &str, multiplier: &str, divisor: &str) -> super::serde_json::Value {
+        let mut map = super::serde_json::Map::new();
+        map.insert("url".to_string(), "https://api.robinhood.com/instruments/x/splits/y/".into());
+        map.insert("instrument".to_string(), "https://api.robinhood.com/instruments/x/".into());
+        map.insert("execution_date".to_string(), date.into());
+        map.insert("multiplier".to_string(), multiplier.into());
+        map.insert("divisor".to_string(), divisor.into());
+        super::serde_json::Value::Object(map)
+    }
+
+    fn history() -> SplitHistory {
+        SplitHistory::new(vec![
+            split("2014-06-09", "7.00000000", "1.00000000"),
+            split("2020-08-31", "4.00000000", "1.00000000"),
+        ])
+    }
+
+    #[test]
+    fn factor_since() {
+        let history = history();
+        assert_eq!(history.factor_since(NaiveDate::from_ymd(2010, 1, 4)).unwrap(), Decimal::from(28u64));
+        assert_eq!(history.factor_since(NaiveDate::from_ymd(2018, 1, 2)).unwrap(), Decimal::from(4u64));
+        assert_eq!(history.factor_since(NaiveDate::from_ymd(2020, 8, 31)).unwrap(), Decimal::from(1u64));
+    }
+
+    #[test]
+    fn reverse_split() {
+        let history = SplitHistory::new(vec![split("2019-01-02", "1.00000000", "10.00000000")]);
+        let date = NaiveDate::from_ymd(2018, 12, 31);
+        assert_eq!(
+            history.adjust_price(Price::from_str("1.5").unwrap(), date).unwrap(),
+            Price::from(15)
+        );
+        assert_eq!(history.adjust_quantity(Quantity::from(100), date).unwrap(), Quantity::from(10));
+    }
+
+    #[test]
+    fn adjust_bars() {
+        let bar = HistoricalBar {
+            begins_at: Utc.ymd(2020, 8, 28).and_hms(0, 0, 0),
+            open_price: Price::from_str("504.050000").unwrap(),
+            close_price: Price::from_str("499.230000").unwrap(),
+            high_price: Price::from_str("505.770000").unwrap(),
+            low_price: Price::from_str("498.310000").unwrap(),
+            volume: 46907479,
+            session: "reg".to_string(),
+            interpolated: false,
+            extra: Default::default(),
+        };
+        let mut evening = bar.clone();
+        // 8:30pm EDT on the 30th, though it's already the 31st in UTC
+        evening.begins_at = Utc.ymd(2020, 8, 31).and_hms(0, 30, 0);
+        let mut split_day = bar.clone();
+        split_day.begins_at = Utc.ymd(2020, 8, 31).and_hms(0, 0, 0);
+
+        let adjusted = history().adjust_bars(&[bar, evening, split_day]).unwrap();
+        assert_eq!(adjusted[0].open_price, Price::from_str("126.0125").unwrap());
+        assert_eq!(adjusted[0].volume, 187629916);
+        assert_eq!(adjusted[1].open_price, adjusted[0].open_price);
+        assert_eq!(adjusted[2].open_price, Price::from_str("504.050000").unwrap());
+    }
+
+    #[test]
+    fn adjust_execution() {
+        let mut fill = execution("e1", "10", "500");
+        // 9pm EDT the night before the split
+        fill.timestamp = Utc.ymd(2020, 8, 31).and_hms(1, 0, 0);
+        let adjusted = history().adjust_execution(&fill).unwrap();
+        assert_eq!(adjusted.price, Price::from(125));
+        assert_eq!(adjusted.quantity, Quantity::from(40));
+    }
+}
+
+/// Latest prices for an instrument
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    pub ask_price: Option<Price>,
+    pub ask_size: u64,
+    pub bid_price: Option<Price>,
+    pub bid_size: u64,
+    pub last_trade_price: Price,
+    pub last_extended_hours_trade_price: Option<Price>,
+    pub previous_close: Price,
+    pub adjusted_previous_close: Price,
+    pub previous_close_date: NaiveDate,
+    pub symbol: String,
+    pub trading_halted: bool,
+    pub has_traded: bool,
+    pub last_trade_price_source: String,
+    pub updated_at: DateTime<Utc>,
+    pub instrument: String,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for Quote {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("Quote", &self.extra)
+    }
+}
+
+impl Quote {
+    /// The price an order on this side would trade against: the ask for buys, the bid for
+    /// sells. Falls back to the last trade when that side of the book is empty.
+    pub fn reference_price(&self, side: &Side) -> Price {
+        let quoted = match *side {
+            Side::Buy => self.ask_price,
+            Side::Sell => self.bid_price,
+            Side::Unknown(_) => None,
+        };
+        match quoted {
+            Some(price) if !price.is_zero() => price,
+            _ => self.last_trade_price,
+        }
+    }
+
+    /// Limit price for a market order: the reference price moved `percent` against us, then
+    /// brought back inside the collar onto a valid tick
+    pub fn collared_price(&self, side: &Side, percent: Decimal, min_tick_size: Option<Price>) -> Price {
+        _collar(self.reference_price(side), side, percent, min_tick_size)
+    }
+}
+
+/// `reference` moved `percent` against an order on `side`, rounded back inside onto a tick
+fn _collar(reference: Price, side: &Side, percent: Decimal, min_tick_size: Option<Price>) -> Price {
+    let reference = reference.value();
+    let offset = reference * percent / Decimal::from(100u64);
+    let (price, rounding) = match *side {
+        Side::Sell => (Price(reference - offset), TickRounding::Up),
+        _ => (Price(reference + offset), TickRounding::Down),
+    };
+    let tick_size = price.tick_size(min_tick_size);
+    price.round_to_tick(tick_size, rounding).unwrap_or(price)
+}
+
+#[cfg(test)]
+mod test_quotes {
+    use super::{Decimal, Price, Quote, Side};
+    use super::serde_json;
+    use std::str::FromStr;
+
+    fn quote(ask: &str, bid: &str) -> Quote {
+        let json = format!(
+            r#"{{
+                "ask_price": {},
+                "ask_size": 100,
+                "bid_price": {},
+                "bid_size": 200,
+                "last_trade_price": "10.0000",
+                "last_extended_hours_trade_price": null,
+                "previous_close": "9.5000",
+                "adjusted_previous_close": "9.5000",
+                "previous_close_date": "2018-06-01",
+                "symbol": "XYZ",
+                "trading_halted": false,
+                "has_traded": true,
+                "last_trade_price_source": "consolidated",
+                "updated_at": "2018-06-04T15:00:00Z",
+                "instrument": "https://api.robinhood.com/instruments/x/"
+            }}"#,
+            ask, bid
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn collared_price() {
+        let quote = quote(r#""10.1300""#, r#""10.0700""#);
+        let five = Decimal::from(5u64);
+        // 10.13 * 1.05 = 10.6365, rounded down into the collar
+        assert_eq!(quote.collared_price(&Side::Buy, five, None), Price::from_str("10.63").unwrap());
+        // 10.07 * 0.95 = 9.5665, rounded up into the collar
+        assert_eq!(quote.collared_price(&Side::Sell, five, None), Price::from_str("9.57").unwrap());
+    }
+
+    #[test]
+    fn empty_book() {
+        let quote = quote("null", "null");
+        assert_eq!(quote.reference_price(&Side::Buy), Price::from_str("10").unwrap());
+        assert_eq!(quote.reference_price(&Side::Sell), Price::from_str("10").unwrap());
+    }
+}
+
+/// Which trading session a moment falls in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    PreMarket,
+    Regular,
+    AfterHours,
+    Closed,
+}
+
+/// A market's hours for a single day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketHours {
+    pub date: NaiveDate,
+    pub is_open: bool,
+    pub opens_at: Option<DateTime<Utc>>,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub extended_opens_at: Option<DateTime<Utc>>,
+    pub extended_closes_at: Option<DateTime<Utc>>,
+    pub previous_open_hours: String,
+    pub next_open_hours: String,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for MarketHours {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("MarketHours", &self.extra)
+    }
+}
+
+impl MarketHours {
+    pub fn session_at(&self, at: DateTime<Utc>) -> Session {
+        if !self.is_open {
+            return Session::Closed;
+        }
+        let during = |opens: Option<DateTime<Utc>>, closes: Option<DateTime<Utc>>| match (opens, closes) {
+            (Some(opens), Some(closes)) => at >= opens && at < closes,
+            _ => false,
+        };
+        if during(self.opens_at, self.closes_at) {
+            Session::Regular
+        } else if during(self.extended_opens_at, self.opens_at) {
+            Session::PreMarket
+        } else if during(self.closes_at, self.extended_closes_at) {
+            Session::AfterHours
+        } else {
+            Session::Closed
+        }
+    }
+}
+
+/// A moment on the market's clock: US Eastern time, daylight saving included
+pub fn market_time(at: DateTime<Utc>) -> DateTime<Tz> {
+    at.with_timezone(&New_York)
+}
+
+/// The trading day a moment belongs to
+pub fn market_date(at: DateTime<Utc>) -> NaiveDate {
+    market_time(at).date().naive_local()
+}
+
+#[cfg(test)]
+mod test_market_hours {
+    use super::{market_date, market_time, MarketHours, Session};
+    use super::serde_json;
+    use chrono::prelude::*;
+
+    fn hours() -> MarketHours {
+        serde_json::from_str(
+            r#"{
+                "date": "2018-06-04",
+                "is_open": true,
+                "opens_at": "2018-06-04T13:30:00Z",
+                "closes_at": "2018-06-04T20:00:00Z",
+                "extended_opens_at": "2018-06-04T13:00:00Z",
+                "extended_closes_at": "2018-06-04T22:00:00Z",
+                "previous_open_hours": "https://api.robinhood.com/markets/XNAS/hours/2018-06-01/",
+                "next_open_hours": "https://api.robinhood.com/markets/XNAS/hours/2018-06-05/"
+            }"#,
+        ).unwrap()
+    }
+
+    #[test]
+    fn sessions() {
+        let hours = hours();
+        let at = |h, m| Utc.ymd(2018, 6, 4).and_hms(h, m, 0);
+        assert_eq!(hours.session_at(at(12, 0)), Session::Closed);
+        assert_eq!(hours.session_at(at(13, 15)), Session::PreMarket);
+        assert_eq!(hours.session_at(at(15, 0)), Session::Regular);
+        assert_eq!(hours.session_at(at(21, 0)), Session::AfterHours);
+        assert_eq!(hours.session_at(at(23, 0)), Session::Closed);
+    }
+
+    #[test]
+    fn trading_day() {
+        // 9pm Eastern is already tomorrow in UTC
+        assert_eq!(
+            market_date(Utc.ymd(2018, 6, 5).and_hms(1, 0, 0)),
+            NaiveDate::from_ymd(2018, 6, 4)
+        );
+        // 12:30am EDT, in time for that morning's pre-market
+        assert_eq!(
+            market_date(Utc.ymd(2018, 6, 5).and_hms(4, 30, 0)),
+            NaiveDate::from_ymd(2018, 6, 5)
+        );
+        // 11:30pm EST
+        assert_eq!(
+            market_date(Utc.ymd(2018, 1, 5).and_hms(4, 30, 0)),
+            NaiveDate::from_ymd(2018, 1, 4)
+        );
+    }
+
+    #[test]
+    fn daylight_saving() {
+        assert_eq!(
+            market_time(Utc.ymd(2018, 6, 4).and_hms(14, 35, 0)).time(),
+            NaiveTime::from_hms(10, 35, 0)
+        );
+        assert_eq!(
+            market_time(Utc.ymd(2018, 1, 4).and_hms(14, 35, 0)).time(),
+            NaiveTime::from_hms(9, 35, 0)
+        );
+    }
+}
+
+/// A single candle from a historicals endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalBar {
+    pub begins_at: DateTime<Utc>,
+    pub open_price: Price,
+    pub close_price: Price,
+    pub high_price: Price,
+    pub low_price: Price,
+    pub volume: u64,
+    pub session: String,
+    pub interpolated: bool,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for HistoricalBar {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("HistoricalBar", &self.extra)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Historicals {
+    pub quote: String,
+    pub symbol: String,
+    pub interval: String,
+    pub span: String,
+    pub bounds: String,
+    pub previous_close_price: Option<Price>,
+    pub open_price: Option<Price>,
+    pub open_time: Option<DateTime<Utc>>,
+    pub instrument: String,
+    pub historicals: Vec<HistoricalBar>,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for Historicals {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("Historicals", &self.extra)?;
+        self.historicals.ensure_known_fields()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarginBalances {
+    pub day_trade_buying_power: Decimal,
+    pub start_of_day_overnight_buying_power: Decimal,
+    pub overnight_buying_power_held_for_orders: Decimal,
+    pub cash_held_for_orders: Decimal,
+    pub created_at: DateTime<Utc>,
+    pub unsettled_debit: Decimal,
+    pub start_of_day_dtbp: Decimal,
+    pub day_trade_buying_power_held_for_orders: Decimal,
+    pub overnight_buying_power: Decimal,
+    pub marked_pattern_day_trader_date: Option<NaiveDate>,
+    pub cash: Decimal,
+    pub unallocated_margin_cash: Decimal,
+    pub updated_at: DateTime<Utc>,
+    pub cash_available_for_withdrawal: Decimal,
+    pub margin_limit: Decimal,
+    pub outstanding_interest: Decimal,
+    pub uncleared_deposits: Decimal,
+    pub unsettled_funds: Decimal,
+    pub gold_equity_requirement: Decimal,
+    pub day_trade_ratio: Decimal,
+    pub overnight_ratio: Decimal,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for MarginBalances {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("MarginBalances", &self.extra)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstantEligibility {
+    updated_at: Option<DateTime<Utc>>,
+    reason: String,
+    reinstatement_date: Option<DateTime<Utc>>,
+    reversal: Option<serde_json::Value>,
+    state: String,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for InstantEligibility {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("InstantEligibility", &self.extra)
+    }
+}
+
+iter_builder!(
+    Accounts => Account as AccountData, "https://api.robinhood.com/accounts/" {
+    deactivated: bool = None,
+    updated_at: DateTime<Utc> = None,
+    margin_balances: MarginBalances = None,
+    #[serde(rename = "portfolio")]
+    portfolio_url: String = None,
+    cash_balances: serde_json::Value = None,
+    can_downgrade_to_cash: String = None,
+    withdrawal_halted: bool = None,
+    cash_available_for_withdrawal: Decimal = None,
+    #[serde(rename = "type")]
+    type_field: String = None,
+    sma: Decimal = None,
+    sweep_enabled: bool = None,
+    deposit_halted: bool = None,
+    buying_power: Decimal = None,
+    user: String = None,
+    max_ach_early_access_amount: Decimal = None,
+    instant_eligibility: InstantEligibility = None,
+    cash_held_for_orders: Decimal = None,
+    only_position_closing_trades: bool = None,
+    url: String = None,
+    positions: String = None,
+    created_at: DateTime<Utc> = None,
+    cash: Decimal = None,
+    sma_held_for_orders: Decimal = None,
+    unsettled_debit: Decimal = None,
+    account_number: String = None,
+    uncleared_deposits: Decimal = None,
+    unsettled_funds: Decimal = None,
+    nummus_enabled: Option<bool> = None, // Crypto
+    option_level: String = None,
+    is_pinnacle_account: bool = None
+} nested { margin_balances, instant_eligibility });
+
+impl Account {
+    /// Grabs this account's portfolio
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client used to make the request
+    pub fn portfolio(&self, client: &Client) -> Result<Portfolio, Error> {
+        let data = client._get_json::<PortfolioData>("portfolio", &self.portfolio_url())?;
+        Ok(Portfolio::new(data))
+    }
+
+    /// Grabs this account's equity history
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client used to make the request
+    /// * `interval` - `5minute`, `10minute`, `hour`, `day`, or `week`
+    /// * `span` - `day`, `week`, `month`, `3month`, `year`, `5year`, or `all`
+    pub fn portfolio_historicals(
+        &self,
+        client: &Client,
+        interval: &str,
+        span: &str,
+    ) -> Result<PortfolioHistoricals, Error> {
+        let url = Url::parse_with_params(
+            &format!(
+                "https://api.robinhood.com/portfolios/historicals/{}/",
+                self.account_number()
+            ),
+            &[("interval", interval), ("span", span)],
+        )?;
+        client._get_json::<PortfolioHistoricals>("portfolio historicals", url.as_str())
+    }
+}
+
+iter_builder!(
+    Portfolios => Portfolio as PortfolioData, "https://api.robinhood.com/portfolios/" {
+    url: String = None,
+    account: String = None,
+    start_date: NaiveDate = None,
+    market_value: Decimal = None,
+    equity: Decimal = None,
+    extended_hours_market_value: Option<Decimal> = None,
+    extended_hours_equity: Option<Decimal> = None,
+    extended_hours_portfolio_equity: Option<Decimal> = None,
+    last_core_market_value: Decimal = None,
+    last_core_equity: Decimal = None,
+    last_core_portfolio_equity: Decimal = None,
+    excess_margin: Decimal = None,
+    excess_maintenance: Decimal = None,
+    excess_margin_with_uncollected_deposits: Decimal = None,
+    excess_maintenance_with_uncollected_deposits: Decimal = None,
+    equity_previous_close: Decimal = None,
+    portfolio_equity_previous_close: Decimal = None,
+    adjusted_equity_previous_close: Decimal = None,
+    adjusted_portfolio_equity_previous_close: Decimal = None,
+    withdrawable_amount: Decimal = None,
+    unwithdrawable_deposits: Decimal = None,
+    unwithdrawable_grants: Decimal = None
+});
+
+impl Portfolio {
+    /// Change in equity since the previous close, using extended hours equity when the market
+    /// is outside of regular hours
+    pub fn day_change(&self) -> Decimal {
+        let equity = self.data.extended_hours_equity.unwrap_or(self.data.equity);
+        equity - self.data.adjusted_equity_previous_close
+    }
+}
+
+/// A single point of equity history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EquityBar {
+    pub begins_at: DateTime<Utc>,
+    pub open_equity: Decimal,
+    pub close_equity: Decimal,
+    pub adjusted_open_equity: Decimal,
+    pub adjusted_close_equity: Decimal,
+    pub open_market_value: Decimal,
+    pub close_market_value: Decimal,
+    pub net_return: Decimal,
+    pub session: String,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for EquityBar {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("EquityBar", &self.extra)
+    }
+}
+
+impl EquityBar {
+    /// Profit or loss over this bar, ignoring deposits and withdrawals
+    pub fn profit_and_loss(&self) -> Decimal {
+        self.adjusted_close_equity - self.adjusted_open_equity
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioHistoricals {
+    pub interval: String,
+    pub span: String,
+    pub bounds: String,
+    pub open_time: Option<DateTime<Utc>>,
+    pub open_equity: Option<Decimal>,
+    pub adjusted_open_equity: Option<Decimal>,
+    pub previous_close_equity: Option<Decimal>,
+    pub adjusted_previous_close_equity: Option<Decimal>,
+    pub total_return: Option<Decimal>,
+    pub equity_historicals: Vec<EquityBar>,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for PortfolioHistoricals {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("PortfolioHistoricals", &self.extra)?;
+        self.equity_historicals.ensure_known_fields()?;
+        Ok(())
+    }
+}
+
+iter_builder!(
+    Dividends => Dividend as DividendData, "https://api.robinhood.com/dividends/" {
+    id: String = None,
+    url: String = None,
+    account: String = None,
+    instrument: String = None,
+    amount: Decimal = None,
+    rate: Decimal = None,
+    position: Quantity = None,
+    withholding: Decimal = None,
+    nra_withholding: Option<Decimal> = None,
+    record_date: NaiveDate = None,
+    payable_date: NaiveDate = None,
+    paid_at: Option<DateTime<Utc>> = None,
+    state: String = None,
+    drip_enabled: Option<bool> = None
+});
+
+impl Dividend {
+    /// Whether the cash has actually landed (or been reinvested)
+    pub fn is_paid(&self) -> bool {
+        self.data.state == "paid" || self.data.state == "reinvested"
+    }
+}
+
+/// Dividend income totals, keyed by symbol, `(year, month)`, and year
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DividendIncome {
+    pub by_symbol: BTreeMap<String, Decimal>,
+    pub by_month: BTreeMap<(i32, u32), Decimal>,
+    pub by_year: BTreeMap<i32, Decimal>,
+    /// Gross income across every dividend counted
+    pub total: Decimal,
+    /// Tax withheld across every dividend counted
+    pub withholding: Decimal,
+}
+
+impl DividendIncome {
+    pub fn new() -> DividendIncome {
+        DividendIncome::default()
+    }
+
+    /// Counts a dividend toward the totals under the given symbol and its payable date
+    pub fn add(&mut self, symbol: &str, dividend: &Dividend) {
+        let amount = dividend.data.amount;
+        let withholding =
+            dividend.data.withholding + dividend.data.nra_withholding.unwrap_or_default();
+        let date = dividend.data.payable_date;
+
+        *self.by_symbol.entry(symbol.to_owned()).or_insert_with(Decimal::default) += amount;
+        *self.by_month.entry((date.year(), date.month())).or_insert_with(Decimal::default) += amount;
+        *self.by_year.entry(date.year()).or_insert_with(Decimal::default) += amount;
+        self.total += amount;
+        self.withholding += withholding;
+    }
+
+    /// Income left after withholding
+    pub fn net(&self) -> Decimal {
+        self.total - self.withholding
+    }
+}
+
+#[cfg(test)]
+mod test_dividends {
+    use super::{Decimal, Dividend, DividendData, DividendIncome};
+    use std::str::FromStr;
+
+    fn dividend(amount: &str, payable_date: &str, state: &str) -> Dividend {
+        let json = format!(
+            r#"{{
+                "id": "d",
+                "url": "https://api.robinhood.com/dividends/d/",
+                "account": "https://api.robinhood.com/accounts/a/",
+                "instrument": "https://api.robinhood.com/instruments/i/",
+                "amount": "{}",
+                "rate": "0.5000000000",
+                "position": "10.0000",
+                "withholding": "0.10",
+                "record_date": "2018-05-14",
+                "payable_date": "{}",
+                "paid_at": null,
+                "state": "{}"
+            }}"#,
+            amount, payable_date, state
+        );
+        Dividend::new(super::serde_json::from_str::<DividendData>(&json).unwrap())
+    }
+
+    #[test]
+    fn income_totals() {
+        let mut income = DividendIncome::new();
+        income.add("MSFT", &dividend("4.20", "2018-06-14", "paid"));
+        income.add("MSFT", &dividend("4.20", "2018-09-13", "paid"));
+        income.add("T", &dividend("5.00", "2019-02-01", "reinvested"));
+
+        let dec = |s: &str| Decimal::from_str(s).unwrap();
+        assert_eq!(income.by_symbol["MSFT"], dec("8.40"));
+        assert_eq!(income.by_month[&(2018, 6)], dec("4.20"));
+        assert_eq!(income.by_year[&2019], dec("5.00"));
+        assert_eq!(income.total, dec("13.40"));
+        assert_eq!(income.net(), dec("13.10"));
+    }
+
+    #[test]
+    fn pending_is_not_paid() {
+        assert!(!dividend("4.20", "2018-06-14", "pending").is_paid());
+        assert!(dividend("4.20", "2018-06-14", "reinvested").is_paid());
+    }
+}
+
+string_enum!(
+    /// Whether an order buys or sells
+    Side {
+    Buy => "buy",
+    Sell => "sell"
+});
+
+string_enum!(
+    /// How an order is priced once it's triggered
+    OrderType {
+    Market => "market",
+    Limit => "limit"
+});
+
+string_enum!(
+    /// How long an order stays working
+    TimeInForce {
+    /// Good for day
+    GoodForDay => "gfd",
+    /// Good 'til cancelled
+    GoodTilCancelled => "gtc",
+    /// Immediate or cancel
+    ImmediateOrCancel => "ioc",
+    /// Fill or kill
+    FillOrKill => "fok",
+    /// Market on open
+    Opening => "opg"
+});
+
+string_enum!(
+    /// What releases an order to the market
+    Trigger {
+    Immediate => "immediate",
+    Stop => "stop"
+});
+
+string_enum!(
+    /// Where an order is in its lifecycle
+    OrderState {
+    Queued => "queued",
+    Unconfirmed => "unconfirmed",
+    Confirmed => "confirmed",
+    PartiallyFilled => "partially_filled",
+    Filled => "filled",
+    Rejected => "rejected",
+    Cancelled => "cancelled",
+    Failed => "failed"
+});
+
+impl OrderState {
+    /// Whether the order is done for good and will never change state again
+    pub fn is_terminal(&self) -> bool {
+        match *self {
+            OrderState::Filled
+            | OrderState::Rejected
+            | OrderState::Cancelled
+            | OrderState::Failed => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_order_enums {
+    use super::{OrderState, OrderType, Side, TimeInForce, Trigger};
+    use super::serde_json;
+
+    #[test]
+    fn round_trip() {
+        let json = r#"["sell","limit","ioc","stop","partially_filled"]"#;
+        let parsed: (Side, OrderType, TimeInForce, Trigger, OrderState) =
+            serde_json::from_str(json).unwrap();
+        assert_eq!(
+            parsed,
+            (
+                Side::Sell,
+                OrderType::Limit,
+                TimeInForce::ImmediateOrCancel,
+                Trigger::Stop,
+                OrderState::PartiallyFilled
+            )
+        );
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn unknown_values() {
+        let state: OrderState = serde_json::from_str(r#""pending_review""#).unwrap();
+        assert_eq!(state, OrderState::Unknown("pending_review".to_string()));
+        assert_eq!(serde_json::to_string(&state).unwrap(), r#""pending_review""#);
+        assert!(!state.is_terminal());
+    }
+}
+
+/// One fill of an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Execution {
+    pub timestamp: DateTime<Utc>,
+    pub price: Price,
+    pub settlement_date: NaiveDate,
+    pub id: String,
+    pub quantity: Quantity,
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KnownFields for Execution {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields("Execution", &self.extra)
+    }
+}
+
+#[cfg(test)]
+mod test_executions {
+    use super::test_fixtures::{execution, order};
+    use super::{Decimal, Price, Quantity};
+    use chrono::prelude::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn parsed() {
+        let execution = execution("e1", "4.00000", "10.00000000");
+        assert_eq!(execution.timestamp, Utc.ymd(2018, 6, 4).and_hms_micro(14, 0, 1, 0));
+        assert_eq!(execution.settlement_date, NaiveDate::from_ymd(2018, 6, 6));
+        assert_eq!(execution.quantity, Quantity::from(4));
+    }
+
+    #[test]
+    fn fills() {
+        let mut buy = order("1", "aapl", "buy", "filled", "10", "2018-06-04T14:00:00Z");
+        assert_eq!(buy.vwap(), None);
+        assert_eq!(buy.slippage(), None);
+
+        buy.data.executions.push(execution("e1", "4", "9.90"));
+        buy.data.executions.push(execution("e2", "6", "10.05"));
+        assert_eq!(buy.filled_quantity(), Quantity::from(10));
+        assert_eq!(buy.vwap(), Some(Price::from_str("9.99").unwrap()));
+        // Limit was $10, so a penny better than asked
+        assert_eq!(buy.slippage(), Some(Decimal::from_str("-0.01").unwrap()));
+
+        let mut sell = order("2", "aapl", "sell", "filled", "10", "2018-06-04T14:00:00Z");
+        sell.data.executions = buy.data.executions.clone();
+        assert_eq!(sell.slippage(), Some(Decimal::from_str("0.01").unwrap()));
+    }
+}
+
+iter_builder!(
+    Orders => Order as OrderData, "https://api.robinhood.com/orders/" {
+    account: String = None,
+    average_price: Option<Price> = None,
+    #[serde(rename = "cancel")]
+    can_cancel: Option<String> = None,
+    created_at: DateTime<Utc> = None,
+    cumulative_quantity: Quantity = None,
+    executions: Vec<Execution> = Vec::new(),
+    extended_hours: bool = None,
+    fees: Decimal = None,
+    id: String = None,
+    instrument: String = None,
+    last_transaction_at: DateTime<Utc> = None,
+    override_day_trade_checks: bool = None,
+    override_dtbp_checks: bool = None,
+    position: String = None,
+    price: Option<Price> = None,
+    quantity: Quantity = None,
+    ref_id: Option<String> = None,
+    reject_reason: Option<String> = None,
+    response_category: Option<String> = None,
+    side: Side = None,
+    state: OrderState = None,
+    stop_price: Option<Price> = None,
+    stop_triggered_at: Option<DateTime<Utc>> = None,
+    time_in_force: TimeInForce = None,
+    trailing_peg: Option<TrailingPeg> = None,
+    last_trail_price: Option<DollarAmount> = None,
+    last_trail_price_updated_at: Option<DateTime<Utc>> = None,
+    trigger: Trigger = None,
+    #[serde(rename = "type")]
+    type_field: OrderType = None,
+    updated_at: DateTime<Utc> = None,
+    url: String = None
+} nested { executions });
+
+/// Narrows down the order history
+///
+/// Time and instrument filters are sent to Robinhood so it skips whole pages for us; the rest
+/// are applied as orders come back.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// extern crate chrono;
+/// extern crate robinhood;
+///
+/// use chrono::prelude::*;
+/// use robinhood::{Client, OrdersQuery, Side};
+/// let rh = Client::new().login("username", "password").build().unwrap();
+/// let midnight = Utc::now().date().and_hms(0, 0, 0);
+/// let todays_buys = rh.query_orders(OrdersQuery::new().updated_since(midnight).side(Side::Buy));
+/// for order in todays_buys {
+///     println!("Order: {:#?}", order);
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrdersQuery {
+    updated_since: Option<DateTime<Utc>>,
+    updated_before: Option<DateTime<Utc>>,
+    instrument: Option<String>,
+    account: Option<String>,
+    states: Vec<OrderState>,
+    open_only: bool,
+    side: Option<Side>,
+}
+
+impl OrdersQuery {
+    pub fn new() -> OrdersQuery {
+        OrdersQuery::default()
+    }
+
+    /// Only orders updated at or after this
+    pub fn updated_since(&mut self, since: DateTime<Utc>) -> &mut OrdersQuery {
+        self.updated_since = Some(since);
+        self
+    }
+
+    /// Only orders updated at or before this
+    pub fn updated_before(&mut self, before: DateTime<Utc>) -> &mut OrdersQuery {
+        self.updated_before = Some(before);
+        self
+    }
+
+    pub fn instrument(&mut self, instrument: &Instrument) -> &mut OrdersQuery {
+        self.instrument = Some(instrument.url());
+        self
+    }
+
+    pub fn account(&mut self, account: &Account) -> &mut OrdersQuery {
+        self.account = Some(account.url());
+        self
+    }
+
+    /// Only orders in this state. Call it more than once to allow several.
+    pub fn state(&mut self, state: OrderState) -> &mut OrdersQuery {
+        self.states.push(state);
+        self
+    }
+
+    /// Only orders that are still working
+    pub fn open(&mut self) -> &mut OrdersQuery {
+        self.open_only = true;
+        self
+    }
+
+    pub fn side(&mut self, side: Side) -> &mut OrdersQuery {
+        self.side = Some(side);
+        self
+    }
+
+    /// First page to ask for, with the server-side filters applied
+    pub fn url(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(since) = self.updated_since {
+            params.push(("updated_at[gte]", since.to_rfc3339()));
+        }
+        if let Some(before) = self.updated_before {
+            params.push(("updated_at[lte]", before.to_rfc3339()));
+        }
+        if let Some(ref instrument) = self.instrument {
+            params.push(("instrument", instrument.to_owned()));
+        }
+        let mut url = Url::parse("https://api.robinhood.com/orders/").unwrap();
+        if !params.is_empty() {
+            url.query_pairs_mut().extend_pairs(&params);
+        }
+        url.to_string()
+    }
+
+    /// Whether an order passes every filter
+    pub fn matches(&self, order: &Order) -> bool {
+        let data = &order.data;
+        self.updated_since.map_or(true, |since| data.updated_at >= since)
+            && self.updated_before.map_or(true, |before| data.updated_at <= before)
+            && self.instrument.as_ref().map_or(true, |url| data.instrument == *url)
+            && self.account.as_ref().map_or(true, |url| data.account == *url)
+            && (self.states.is_empty() || self.states.contains(&data.state))
+            && (!self.open_only || !data.state.is_terminal())
+            && self.side.as_ref().map_or(true, |side| data.side == *side)
+    }
+}
+
+/// Orders that match an `OrdersQuery`
+#[derive(Debug, Clone)]
+pub struct QueriedOrders {
+    orders: Orders,
+    query: OrdersQuery,
+}
+
+impl QueriedOrders {
+    pub fn new_with_client(client: HTTPClient, query: &OrdersQuery) -> QueriedOrders {
+        QueriedOrders {
+            orders: Orders::new_with_client(client)
+                .set_next(query.url())
+                .to_owned(),
+            query: query.to_owned(),
+        }
+    }
+}
+
+impl Iterator for QueriedOrders {
+    type Item = Result<Order, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.orders.next() {
+                Some(Ok(order)) => {
+                    if self.query.matches(&order) {
+                        return Some(Ok(order));
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+// Canned API data shared by the tests below
+#[cfg(test)]
+mod test_fixtures {
+    use super::{
+        Account, AccountData, Execution, HTTPClient, Instrument, InstrumentData, Order, OrderBuilder,
+        OrderData, OrderPayload, Quantity, Side,
+    };
+    use super::serde_json;
+    use std::str::FromStr;
+
+    /// A tradable NYSE stock, fractional shares and all
+    pub fn instrument(symbol: &str) -> Instrument {
+        let json = format!(
+            r#"{{
+                "min_tick_size": null,
+                "type": "stock",
+                "splits": "https://api.robinhood.com/instruments/{id}/splits/",
+                "margin_initial_ratio": "0.5000",
+                "url": "https://api.robinhood.com/instruments/{id}/",
+                "quote": "https://api.robinhood.com/quotes/{symbol}/",
+                "tradability": "tradable",
+                "bloomberg_unique": "EQ0000000000000000",
+                "list_date": "1990-01-02",
+                "name": "{symbol} Inc.",
+                "symbol": "{symbol}",
+                "fundamentals": "https://api.robinhood.com/fundamentals/{symbol}/",
+                "state": "active",
+                "country": "US",
+                "day_trade_ratio": "0.2500",
+                "tradeable": true,
+                "maintenance_ratio": "0.2500",
+                "id": "{id}",
+                "market": "https://api.robinhood.com/markets/XNYS/",
+                "simple_name": "{symbol}",
+                "rhs_tradability": "tradable",
+                "fractional_tradability": "tradable",
+                "tradable_chain_id": null
+            }}"#,
+            id = symbol.to_lowercase(),
+            symbol = symbol
+        );
+        Instrument::new(serde_json::from_str::<InstrumentData>(&json).unwrap())
+    }
+
+    /// An account of `type_field` (`cash` or `margin`) with $1,000 to spend
+    pub fn account(type_field: &str) -> Account {
+        let json = format!(
+            r#"{{
+                "deactivated": false,
+                "updated_at": "2018-06-04T14:00:00Z",
+                "margin_balances": {{
+                    "day_trade_buying_power": "4000.0000",
+                    "start_of_day_overnight_buying_power": "2000.0000",
+                    "overnight_buying_power_held_for_orders": "0.0000",
+                    "cash_held_for_orders": "0.0000",
+                    "created_at": "2018-06-04T14:00:00Z",
+                    "unsettled_debit": "0.0000",
+                    "start_of_day_dtbp": "4000.0000",
+                    "day_trade_buying_power_held_for_orders": "0.0000",
+                    "overnight_buying_power": "2000.0000",
+                    "marked_pattern_day_trader_date": null,
+                    "cash": "1000.0000",
+                    "unallocated_margin_cash": "1000.0000",
+                    "updated_at": "2018-06-04T14:00:00Z",
+                    "cash_available_for_withdrawal": "1000.0000",
+                    "margin_limit": "0.0000",
+                    "outstanding_interest": "0.0000",
+                    "uncleared_deposits": "0.0000",
+                    "unsettled_funds": "0.0000",
+                    "gold_equity_requirement": "0.0000",
+                    "day_trade_ratio": "0.2500",
+                    "overnight_ratio": "0.5000"
+                }},
+                "portfolio": "https://api.robinhood.com/accounts/5RY82436/portfolio/",
+                "cash_balances": null,
+                "can_downgrade_to_cash": "https://api.robinhood.com/accounts/5RY82436/can_downgrade_to_cash/",
+                "withdrawal_halted": false,
+                "cash_available_for_withdrawal": "1000.0000",
+                "type": "{type_field}",
+                "sma": "0.0000",
+                "sweep_enabled": false,
+                "deposit_halted": false,
+                "buying_power": "1000.0000",
+                "user": "https://api.robinhood.com/user/",
+                "max_ach_early_access_amount": "0.00",
+                "instant_eligibility": {{
+                    "updated_at": null,
+                    "reason": "",
+                    "reinstatement_date": null,
+                    "reversal": null,
+                    "state": "ok"
+                }},
+                "cash_held_for_orders": "0.0000",
+                "only_position_closing_trades": false,
+                "url": "https://api.robinhood.com/accounts/5RY82436/",
+                "positions": "https://api.robinhood.com/accounts/5RY82436/positions/",
+                "created_at": "2018-01-02T14:00:00Z",
+                "cash": "1000.0000",
+                "sma_held_for_orders": "0.0000",
+                "unsettled_debit": "0.0000",
+                "account_number": "5RY82436",
+                "uncleared_deposits": "0.0000",
+                "unsettled_funds": "0.0000",
+                "option_level": "",
+                "is_pinnacle_account": false
+            }}"#,
+            type_field = type_field
+        );
+        Account::new(serde_json::from_str::<AccountData>(&json).unwrap())
+    }
+
+    /// A market order for `quantity` shares of AAPL from a cash account. Nothing it checks
+    /// before reaching for the network needs a login.
+    pub fn builder(side: Side, quantity: &str) -> OrderBuilder {
+        OrderBuilder::new(
+            HTTPClient::new(),
+            side,
+            Quantity::from_str(quantity).unwrap(),
+            instrument("AAPL"),
+            account("cash"),
+        )
+    }
+
+    /// A fill on 2018-06-04, settling two days later
+    pub fn execution(id: &str, quantity: &str, price: &str) -> Execution {
+        serde_json::from_str(&format!(
+            r#"{{
+                "timestamp": "2018-06-04T14:00:01.000000Z",
+                "price": "{}",
+                "settlement_date": "2018-06-06",
+                "id": "{}",
+                "quantity": "{}"
+            }}"#,
+            price, id, quantity
+        )).unwrap()
+    }
+
+    /// An order with no executions, last touched at `at`
+    pub fn order(id: &str, instrument: &str, side: &str, state: &str, filled: &str, at: &str) -> Order {
+        let json = format!(
+            r#"{{
+                "account": "https://api.robinhood.com/accounts/5RY82436/",
+                "average_price": null,
+                "cancel": null,
+                "created_at": "{at}",
+                "cumulative_quantity": "{filled}",
+                "executions": [],
+                "extended_hours": false,
+                "fees": "0.00",
+                "id": "{id}",
+                "instrument": "https://api.robinhood.com/instruments/{instrument}/",
+                "last_transaction_at": "{at}",
+                "override_day_trade_checks": false,
+                "override_dtbp_checks": false,
+                "position": "https://api.robinhood.com/positions/5RY82436/{instrument}/",
+                "price": "10.00000000",
+                "quantity": "10.00000",
+                "side": "{side}",
+                "state": "{state}",
+                "stop_price": null,
+                "time_in_force": "gfd",
+                "trigger": "immediate",
+                "type": "limit",
+                "updated_at": "{at}",
+                "url": "https://api.robinhood.com/orders/{id}/"
+            }}"#,
+            id = id,
+            instrument = instrument,
+            side = side,
+            state = state,
+            filled = filled,
+            at = at
+        );
+        Order::new(serde_json::from_str::<OrderData>(&json).unwrap())
+    }
+
+    /// A limit buy of 10 shares at $10
+    pub fn payload() -> OrderPayload {
+        serde_json::from_str(
+            r#"{
+                "account": "https://api.robinhood.com/accounts/5RY82436/",
+                "instrument": "https://api.robinhood.com/instruments/aapl/",
+                "symbol": "AAPL",
+                "type": "limit",
+                "time_in_force": "gfd",
+                "trigger": "immediate",
+                "side": "buy",
+                "quantity": "10",
+                "price": "10.00",
+                "extended_hours": false,
+                "override_day_trade_checks": false,
+                "override_dtbp_checks": false
+            }"#,
+        ).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test_orders_query {
+    use super::test_fixtures::order;
+    use super::{OrderState, OrdersQuery, Side};
+    use chrono::prelude::*;
+
+    #[test]
+    fn url() {
+        let query = OrdersQuery::new()
+            .updated_since(Utc.ymd(2018, 6, 4).and_hms(0, 0, 0))
+            .side(Side::Buy)
+            .to_owned();
+        assert_eq!(
+            query.url(),
+            "https://api.robinhood.com/orders/?updated_at%5Bgte%5D=2018-06-04T00%3A00%3A00%2B00%3A00"
+        );
+        assert_eq!(OrdersQuery::new().url(), "https://api.robinhood.com/orders/");
+    }
+
+    #[test]
+    fn matches() {
+        let buy = order("1", "aapl", "buy", "filled", "10", "2018-06-04T14:00:00Z");
+        let sell = order("2", "aapl", "sell", "confirmed", "0", "2018-06-05T14:00:00Z");
+
+        let query = OrdersQuery::new().side(Side::Buy).to_owned();
+        assert!(query.matches(&buy) && !query.matches(&sell));
+
+        let query = OrdersQuery::new().open().to_owned();
+        assert!(!query.matches(&buy) && query.matches(&sell));
+
+        let query = OrdersQuery::new()
+            .state(OrderState::Filled)
+            .state(OrderState::Cancelled)
+            .to_owned();
+        assert!(query.matches(&buy) && !query.matches(&sell));
+
+        let query = OrdersQuery::new()
+            .updated_before(Utc.ymd(2018, 6, 5).and_hms(0, 0, 0))
+            .to_owned();
+        assert!(query.matches(&buy) && !query.matches(&sell));
+    }
+}
+
+iter_builder!(
+    Positions => Position as PositionData, "https://api.robinhood.com/accounts/{account_id}/positions/" {
+    shares_held_for_stock_grants: Quantity = None,
+    account: String = None,
+    intraday_quantity: Quantity = None,
+    intraday_average_buy_price: Price = None,
+    url: String = None,
+    created_at: DateTime<Utc> = None,
+    updated_at: DateTime<Utc> = None,
+    shares_held_for_buys: Quantity = None,
+    average_buy_price: Price = None,
+    instrument: String = None,
+    shares_held_for_sells: Quantity = None,
+    quantity: Quantity = None
+});
+
+iter_builder!(
+    Watchlists => Watchlist as WatchlistData, "https://api.robinhood.com/watchlists/" {
+    url: String = None,
+    user: String = None,
+    name: String = None
+});
+
+impl Watchlist {
+    /// Creates a recursive iterator over the instruments on this watchlist
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client used to make the request
+    pub fn instruments(&self, client: &Client) -> WatchlistItems {
+        WatchlistItems::new_with_client(client.client.to_owned())
+            .set_next(self.url())
+            .to_owned()
+    }
+}
+
+iter_builder!(
+    WatchlistItems => WatchlistItem as WatchlistItemData, "https://api.robinhood.com/watchlists/{name}/" {
+    watchlist: String = None,
+    instrument: String = None,
+    created_at: DateTime<Utc> = None,
+    url: String = None
+});
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+}
+
+/// A dollar amount in a specific currency
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DollarAmount {
+    pub amount: Decimal,
+    pub currency_code: String,
+}
+
+string_enum!(
+    /// What a trailing stop's trail is measured in
+    TrailingPegType {
+    Percentage => "percentage",
+    Price => "price"
+});
+
+/// How far a trailing stop follows behind the market
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrailingPeg {
+    #[serde(rename = "type")]
+    pub type_field: TrailingPegType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percentage: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<DollarAmount>,
+}
+
+#[cfg(test)]
+mod test_trailing_peg {
+    use super::{Decimal, TrailingPeg, TrailingPegType};
+    use super::serde_json;
+
+    #[test]
+    fn percentage() {
+        let peg = TrailingPeg {
+            type_field: TrailingPegType::Percentage,
+            percentage: Some(Decimal::from(5u64)),
+            price: None,
+        };
+        let json = serde_json::to_string(&peg).unwrap();
+        assert_eq!(json, r#"{"type":"percentage","percentage":"5"}"#);
+        assert_eq!(serde_json::from_str::<TrailingPeg>(&json).unwrap(), peg);
+    }
+
+    #[test]
+    fn price() {
+        let json = r#"{"type":"price","price":{"amount":"2.00","currency_code":"USD"}}"#;
+        let peg = serde_json::from_str::<TrailingPeg>(json).unwrap();
+        assert_eq!(peg.type_field, TrailingPegType::Price);
+        assert_eq!(peg.price.unwrap().amount, Decimal::new(2, 0));
+    }
+}
+
+/// Exactly what gets posted to `/orders/`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderPayload {
+    pub account: String,
+    pub instrument: String,
+    pub symbol: String,
+    #[serde(rename = "type")]
+    pub type_field: OrderType,
+    pub time_in_force: TimeInForce,
+    pub trigger: Trigger,
+    pub side: Side,
+    pub quantity: Quantity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<Price>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_price: Option<Price>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dollar_based_amount: Option<DollarAmount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_peg: Option<TrailingPeg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ref_id: Option<String>,
+    pub extended_hours: bool,
+    pub override_day_trade_checks: bool,
+    pub override_dtbp_checks: bool,
+}
+
+impl OrderPayload {
+    /// What it would take to place `order` again as it stands
+    pub fn from_order(order: &OrderData, symbol: &str) -> OrderPayload {
+        OrderPayload {
+            account: order.account.to_owned(),
+            instrument: order.instrument.to_owned(),
+            symbol: symbol.to_owned(),
+            type_field: order.type_field.to_owned(),
+            time_in_force: order.time_in_force.to_owned(),
+            trigger: order.trigger.to_owned(),
+            side: order.side.to_owned(),
+            quantity: order.quantity,
+            price: order.price,
+            stop_price: order.stop_price,
+            dollar_based_amount: None,
+            trailing_peg: order.trailing_peg.to_owned(),
+            ref_id: None,
+            extended_hours: order.extended_hours,
+            override_day_trade_checks: order.override_day_trade_checks,
+            override_dtbp_checks: order.override_dtbp_checks,
+        }
+    }
+}
+
+/// Decodes the response to an order submission, keeping Robinhood's reason when it refuses
+fn _order_response(mut res: Response) -> Result<Order, Error> {
+    let status = res.status().as_u16();
+    if status == 404 || status == 405 {
+        return Err(NotFound::new("order endpoint", res.url().as_str()).into());
+    }
+    if !res.status().is_success() {
+        let mut body = String::new();
+        res.read_to_string(&mut body)?;
+        return Err(err_msg(format!("order rejected: {} {}", res.status(), body)));
+    }
+    Ok(Order::new(res.json::<OrderData>()?))
+}
+
+/// Finds the order placed with `ref_id`, looking only at orders touched since `since`
+fn _find_order_by_ref_id(
+    client: &HTTPClient,
+    ref_id: &str,
+    since: DateTime<Utc>,
+) -> Result<Option<Order>, Error> {
+    let orders = QueriedOrders::new_with_client(
+        client.to_owned(),
+        OrdersQuery::new().updated_since(since),
+    );
+    for order in orders {
+        let order = order?;
+        if order.data.ref_id.as_ref().map(String::as_str) == Some(ref_id) {
+            return Ok(Some(order));
+        }
+    }
+    Ok(None)
+}
+
+/// Posts an order, retrying when we can't tell whether it went through
+///
+/// A timeout or server error after the POST leaves the order in limbo. Before each retry the
+/// order is looked up by its `ref_id`, so a retry can never place it twice.
+fn _post_order(client: &HTTPClient, payload: &OrderPayload) -> Result<Order, Error> {
+    _post_order_to(client, "https://api.robinhood.com/orders/", payload)
+}
+
+/// `_post_order` against another endpoint that creates orders, like an order's `replace/`
+fn _post_order_to(client: &HTTPClient, url: &str, payload: &OrderPayload) -> Result<Order, Error> {
+    let ref_id = match payload.ref_id {
+        Some(ref ref_id) => ref_id.to_owned(),
+        None => {
+            let res = client.post(url).json(payload).send()?;
+            return _order_response(res);
+        }
+    };
+
+    let since = Utc::now() - chrono::Duration::minutes(1);
+    _retry_post(
+        &ref_id,
+        Duration::from_secs(1),
+        || match client.post(url).json(payload).send() {
+            Ok(ref res) if res.status().is_server_error() => {
+                Err(err_msg(format!("order request failed: {}", res.status())))
+            }
+            Ok(res) => Ok(_order_response(res)),
+            Err(err) => Err(err.into()),
+        },
+        || _find_order_by_ref_id(client, &ref_id, since),
+    )
+}
+
+/// The retry loop behind `_post_order`
+///
+/// `post` gives Robinhood's answer, or an error when there's no telling whether the order
+/// went through. `lookup` finds the order by its ref_id. Waits `backoff`, then twice that,
+/// between attempts.
+fn _retry_post<P, L>(ref_id: &str, backoff: Duration, mut post: P, mut lookup: L) -> Result<Order, Error>
+where
+    P: FnMut() -> Result<Result<Order, Error>, Error>,
+    L: FnMut() -> Result<Option<Order>, Error>,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let err = match post() {
+            Ok(result) => return result,
+            Err(err) => err,
+        };
+
+        let cause = match lookup() {
+            Ok(Some(order)) => return Ok(order),
+            Ok(None) if attempt < 3 => {
+                thread::sleep(backoff * attempt);
+                continue;
+            }
+            Ok(None) => format!("{}; not found after {} attempts", err, attempt),
+            Err(lookup) => format!("{}; lookup failed: {}", err, lookup),
+        };
+        return Err(OrderUnconfirmed {
+            ref_id: ref_id.to_owned(),
+            cause: cause,
+        }.into());
+    }
+}
+
+#[cfg(test)]
+mod test_post_order {
+    use super::test_fixtures::order;
+    use super::{_retry_post, err_msg, Error, Order, OrderUnconfirmed};
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    fn placed() -> Order {
+        order("1", "aapl", "buy", "confirmed", "0", "2018-06-04T14:00:00Z")
+    }
+
+    fn timeout() -> Result<Result<Order, Error>, Error> {
+        Err(err_msg("timed out"))
+    }
+
+    #[test]
+    fn found_after_timeout() {
+        let posts = Cell::new(0);
+        let result = _retry_post(
+            "ref-1",
+            Duration::from_millis(0),
+            || {
+                posts.set(posts.get() + 1);
+                timeout()
+            },
+            || Ok(Some(placed())),
+        );
+        assert_eq!(result.unwrap().data.id, "1");
+        assert_eq!(posts.get(), 1);
+    }
+
+    #[test]
+    fn retried_when_not_found() {
+        let posts = Cell::new(0);
+        let result = _retry_post(
+            "ref-1",
+            Duration::from_millis(0),
+            || {
+                posts.set(posts.get() + 1);
+                if posts.get() == 1 {
+                    timeout()
+                } else {
+                    Ok(Ok(placed()))
+                }
+            },
+            || Ok(None),
+        );
+        assert!(result.is_ok());
+        assert_eq!(posts.get(), 2);
+    }
+
+    #[test]
+    fn unconfirmed() {
+        let posts = Cell::new(0);
+        let err = _retry_post(
+            "ref-1",
+            Duration::from_millis(0),
+            || {
+                posts.set(posts.get() + 1);
+                timeout()
+            },
+            || Ok(None),
+        ).unwrap_err();
+        assert_eq!(posts.get(), 3);
+        assert_eq!(err.downcast::<OrderUnconfirmed>().unwrap().ref_id, "ref-1");
+
+        let err = _retry_post("ref-1", Duration::from_millis(0), timeout, || Err(err_msg("down")))
+            .unwrap_err();
+        assert!(err.downcast_ref::<OrderUnconfirmed>().is_some());
+    }
+
+    #[test]
+    fn rejected() {
+        // A definite answer is final, even a refusal
+        let lookups = Cell::new(0);
+        let result = _retry_post(
+            "ref-1",
+            Duration::from_millis(0),
+            || Ok(Err(err_msg("order rejected"))),
+            || {
+                lookups.set(lookups.get() + 1);
+                Ok(None)
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(lookups.get(), 0);
+    }
+}
+
+/// Polls an order until `done` is satisfied, journaling every change from `order` on
+///
+/// The wait between requests starts at `poll_interval`, or 100ms if that's shorter, and
+/// doubles each time, up to five seconds (or `poll_interval`, if that's longer).
+fn _poll_order<F>(
+    client: &HTTPClient,
+    order: &OrderData,
+    journal: Option<&Journal>,
+    timeout: Duration,
+    poll_interval: Duration,
+    mut done: F,
+) -> Result<OrderData, Error>
+where
+    F: FnMut(&OrderData) -> bool,
+{
+    let deadline = Instant::now() + timeout;
+    let max_delay = cmp::max(poll_interval, Duration::from_secs(5));
+    let mut delay = cmp::max(poll_interval, Duration::from_millis(100));
+    let url = order.url.as_str();
+    let mut updated_at = order.updated_at;
+    loop {
+        let mut res = client.get(url).send()?;
+        _check_response("order", url, &res)?;
+        let data = res.json::<OrderData>()?;
+        if data.updated_at != updated_at {
+            updated_at = data.updated_at;
+            if let Some(journal) = journal {
+                journal.updated(&Order::new(data.to_owned()));
+            }
+        }
+        if done(&data) {
+            return Ok(data);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(WaitTimeout {
+                last_seen: Order::new(data),
+            }.into());
+        }
+        thread::sleep(cmp::min(delay, deadline - now));
+        delay = cmp::min(delay * 2, max_delay);
+    }
+}
+
+/// Returned when an order doesn't reach the state we were waiting for in time
+#[derive(Debug, Clone)]
+pub struct WaitTimeout {
+    /// The order as it was on the last poll
+    pub last_seen: Order,
+}
+
+impl fmt::Display for WaitTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "timed out waiting on order {} (last seen {})",
+            self.last_seen.data.id, self.last_seen.data.state
+        )
+    }
+}
+
+impl StdError for WaitTimeout {
+    fn description(&self) -> &str {
+        "timed out waiting on order"
+    }
+}
+
+impl Order {
+    /// Grabs the latest copy of this order
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client used to make the request
+    pub fn refresh(&self, client: &Client) -> Result<Order, Error> {
+        let order = Order::new(client._get_json::<OrderData>("order", &self.data.url)?);
+        if let Some(ref journal) = client.journal {
+            if order.data.updated_at != self.data.updated_at {
+                journal.updated(&order);
+            }
+        }
+        Ok(order)
+    }
+
+    /// Blocks until `predicate` holds for the order, polling with backoff
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client used to make the requests
+    /// * `predicate` - Checked against every fresh copy of the order
+    /// * `timeout` - Give up (with a `WaitTimeout`) after this long
+    /// * `poll_interval` - Wait between the first two polls, at least 100ms; it doubles from there
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// use std::time::Duration;
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let order = rh.orders().nth(0).unwrap().unwrap();
+    /// let half_filled = order.wait_until(
+    ///     &rh,
+    ///     |o| o.cumulative_quantity().value() * robinhood::Decimal::from(2u64) >= o.quantity().value(),
+    ///     Duration::from_secs(60),
+    ///     Duration::from_millis(500),
+    /// );
+    /// ```
+    pub fn wait_until<F>(
+        &self,
+        client: &Client,
+        mut predicate: F,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<Order, Error>
+    where
+        F: FnMut(&Order) -> bool,
+    {
+        let mut order = self.to_owned();
+        let data = _poll_order(
+            &client.client,
+            &self.data,
+            client.journal.as_ref(),
+            timeout,
+            poll_interval,
+            |data| {
+                order.data = data.to_owned();
+                predicate(&order)
+            },
+        )?;
+        Ok(Order::new(data))
+    }
+
+    /// Blocks until the order is filled, cancelled, rejected, or failed, and returns it with
+    /// all of its executions
+    pub fn wait_for_terminal_state(&self, client: &Client, timeout: Duration) -> Result<Order, Error> {
+        self.wait_until(
+            client,
+            |order| order.data.state.is_terminal(),
+            timeout,
+            Duration::from_millis(250),
+        )
+    }
+
+    /// Shares filled so far, summed over the executions
+    pub fn filled_quantity(&self) -> Quantity {
+        self.data
+            .executions
+            .iter()
+            .fold(Quantity::default(), |total, execution| total + execution.quantity)
+    }
+
+    /// Volume-weighted average fill price, if anything has filled
+    pub fn vwap(&self) -> Option<Price> {
+        let filled = self.filled_quantity();
+        if filled.is_zero() {
+            return None;
+        }
+        let cost = self
+            .data
+            .executions
+            .iter()
+            .fold(Decimal::default(), |total, execution| total + execution.quantity * execution.price);
+        Some(Price(cost / filled.value()))
+    }
+
+    /// How much worse per share the fills were than the order's price: above it for buys,
+    /// below it for sells. Negative means price improvement.
+    ///
+    /// Market orders are compared against the collared price they were sent with.
+    pub fn slippage(&self) -> Option<Decimal> {
+        let (price, vwap) = match (self.data.price, self.vwap()) {
+            (Some(price), Some(vwap)) => (price.value(), vwap.value()),
+            _ => return None,
+        };
+        match self.data.side {
+            Side::Sell => Some(price - vwap),
+            _ => Some(vwap - price),
+        }
+    }
+}
+
+/// What happened to one order in `Client::cancel_all`
+#[derive(Debug)]
+pub enum CancelOutcome {
+    /// Robinhood accepted the cancellation
+    Cancelled,
+    /// The order finished before it could be cancelled
+    AlreadyTerminal(OrderState),
+    Failed(Error),
+}
+
+/// Per-order results of `Client::cancel_all`
+#[derive(Debug, Default)]
+pub struct CancelReport {
+    pub results: Vec<(Order, CancelOutcome)>,
+}
+
+impl CancelReport {
+    pub fn cancelled(&self) -> Vec<&Order> {
+        self.results
+            .iter()
+            .filter(|&&(_, ref outcome)| match *outcome {
+                CancelOutcome::Cancelled => true,
+                _ => false,
+            })
+            .map(|&(ref order, _)| order)
+            .collect()
+    }
+
+    pub fn failed(&self) -> Vec<(&Order, &Error)> {
+        self.results
+            .iter()
+            .filter_map(|&(ref order, ref outcome)| match *outcome {
+                CancelOutcome::Failed(ref err) => Some((order, err)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// True when nothing is left working that we tried to cancel
+    pub fn is_complete(&self) -> bool {
+        self.failed().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test_cancel_report {
+    use super::test_fixtures::order;
+    use super::{CancelOutcome, CancelReport, OrderState};
+    use failure::err_msg;
+
+    #[test]
+    fn outcomes() {
+        let mut report = CancelReport::default();
+        report.results.push((
+            order("1", "aapl", "buy", "confirmed", "0", "2018-06-04T14:00:00Z"),
+            CancelOutcome::Cancelled,
+        ));
+        report.results.push((
+            order("2", "aapl", "buy", "filled", "10", "2018-06-04T14:00:00Z"),
+            CancelOutcome::AlreadyTerminal(OrderState::Filled),
+        ));
+        assert!(report.is_complete());
+        assert_eq!(report.cancelled()[0].id(), "1");
+
+        report.results.push((
+            order("3", "msft", "sell", "confirmed", "0", "2018-06-04T14:00:00Z"),
+            CancelOutcome::Failed(err_msg("boom")),
+        ));
+        assert!(!report.is_complete());
+        assert_eq!(report.failed()[0].0.id(), "3");
+    }
+}
+
+/// Whole and fractional shares `amount` dollars buys at `price`, rounded down to the 6 places
+/// Robinhood accepts
+fn _shares_for_amount(amount: Decimal, price: Price) -> Result<Quantity, Error> {
+    if price.value() <= Decimal::default() {
+        return Err(InvalidOrder::new("no price to size a dollar-based order from").into());
+    }
+    let shares = amount / price.value();
+    Ok(Quantity(shares.round_dp_with_strategy(6, RoundingStrategy::ToZero)))
+}
+
+/// What to change on a working order. Anything left unset carries over.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderChanges {
+    price: Option<Price>,
+    stop_price: Option<Price>,
+    quantity: Option<Quantity>,
+    allow_day_trade: bool,
+}
+
+impl OrderChanges {
+    pub fn new() -> OrderChanges {
+        OrderChanges::default()
+    }
+
+    pub fn price<P: Into<Price>>(&mut self, price: P) -> &mut OrderChanges {
+        self.price = Some(price.into());
+        self
+    }
+
+    pub fn stop_price<P: Into<Price>>(&mut self, price: P) -> &mut OrderChanges {
+        self.stop_price = Some(price.into());
+        self
+    }
+
+    /// Total size of the order, including anything that's already filled
+    pub fn quantity<Q: Into<Quantity>>(&mut self, quantity: Q) -> &mut OrderChanges {
+        self.quantity = Some(quantity.into());
+        self
+    }
+
+    /// Sends the replacement even if it makes a day trade that would flag the account as a
+    /// pattern day trader; see `OrderBuilder::allow_day_trade`
+    pub fn allow_day_trade(&mut self) -> &mut OrderChanges {
+        self.allow_day_trade = true;
+        self
+    }
+
+    /// Writes whatever was set over `payload`
+    pub fn apply(&self, payload: &mut OrderPayload) {
+        if self.price.is_some() {
+            payload.price = self.price;
+        }
+        if self.stop_price.is_some() {
+            payload.stop_price = self.stop_price;
+        }
+        if let Some(quantity) = self.quantity {
+            payload.quantity = quantity;
+        }
+    }
+}
+
+/// What's left to send of a `total` share order once `filled` shares have gone, if anything
+fn _remaining_quantity(total: Quantity, filled: Quantity) -> Option<Quantity> {
+    let remaining = total.value() - filled.value();
+    if remaining > Decimal::default() {
+        Some(Quantity(remaining))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_order_changes {
+    use super::test_fixtures::payload;
+    use super::{_remaining_quantity, OrderChanges, Price, Quantity};
+    use std::str::FromStr;
+
+    #[test]
+    fn apply() {
+        let mut changed = payload();
+        OrderChanges::new().apply(&mut changed);
+        assert_eq!(changed, payload());
+
+        changed.stop_price = Some(Price::from(9));
+        OrderChanges::new()
+            .price(Price::from_str("10.05").unwrap())
+            .quantity(Quantity::from(20))
+            .apply(&mut changed);
+        assert_eq!(changed.price, Some(Price::from_str("10.05").unwrap()));
+        assert_eq!(changed.stop_price, Some(Price::from(9)));
+        assert_eq!(changed.quantity, Quantity::from(20));
+    }
+
+    #[test]
+    fn remaining() {
+        assert_eq!(
+            _remaining_quantity(Quantity::from(10), Quantity::from_str("3.5").unwrap()),
+            Some(Quantity::from_str("6.5").unwrap())
+        );
+        assert_eq!(_remaining_quantity(Quantity::from(10), Quantity::from(10)), None);
+        // More can fill than a resize asked for
+        assert_eq!(_remaining_quantity(Quantity::from(5), Quantity::from(8)), None);
+    }
+}
+
+/// SEC fee on sales, in dollars per dollar sold
+const SEC_FEE_RATE: (i64, u32) = (13, 6);
+/// FINRA trading activity fee on sales, per share, and its cap per trade
+const TAF_PER_SHARE: (i64, u32) = (119, 6);
+const TAF_MAX: (i64, u32) = (595, 2);
+
+/// Regulatory fees charged on a sale, each rounded up to the cent. Buys are free.
+///
+/// These are the SEC and FINRA rates in effect when this was written; they change from year
+/// to year, so treat the result as an estimate.
+pub fn regulatory_fees(side: &Side, quantity: Quantity, amount: Decimal) -> Decimal {
+    if *side != Side::Sell {
+        return Decimal::default();
+    }
+    let cents = |fee: Decimal| fee.round_dp_with_strategy(2, RoundingStrategy::AwayFromZero);
+    let sec = amount * Decimal::new(SEC_FEE_RATE.0, SEC_FEE_RATE.1);
+    let taf = cmp::min(
+        quantity.value() * Decimal::new(TAF_PER_SHARE.0, TAF_PER_SHARE.1),
+        Decimal::new(TAF_MAX.0, TAF_MAX.1),
+    );
+    cents(sec) + cents(taf)
+}
+
+/// What an order would do, worked out without sending it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderPreview {
+    /// Exactly what `send` would post
+    pub payload: OrderPayload,
+    /// Bid for sells, ask for buys
+    pub quote_price: Price,
+    /// Shares times the limit price, or the quote for market orders
+    pub estimated_amount: Decimal,
+    pub estimated_fees: Decimal,
+    /// What Robinhood sets aside for a buy: shares times the (collared) limit price
+    pub required_buying_power: Decimal,
+    pub buying_power: Decimal,
+    /// Only for margin accounts
+    pub day_trade_buying_power: Option<Decimal>,
+    pub position_before: Quantity,
+    pub position_after: Quantity,
+    /// Whether this sell closes something bought today
+    pub day_trade: bool,
+    /// Day trades already made in the last five trading days
+    pub day_trades: usize,
+}
+
+impl OrderPreview {
+    /// Money in or out once it fills at the estimate: negative for buys
+    pub fn net_amount(&self) -> Decimal {
+        match self.payload.side {
+            Side::Sell => self.estimated_amount - self.estimated_fees,
+            _ => -(self.estimated_amount + self.estimated_fees),
+        }
+    }
+
+    pub fn has_buying_power(&self) -> bool {
+        self.required_buying_power <= self.buying_power
+    }
+}
+
+#[cfg(test)]
+mod test_preview {
+    use super::{regulatory_fees, Decimal, Quantity, Side};
+    use std::str::FromStr;
+
+    #[test]
+    fn fees() {
+        let dollars = |s: &str| Decimal::from_str(s).unwrap();
+        assert_eq!(regulatory_fees(&Side::Buy, Quantity::from(100), dollars("10000")), Decimal::default());
+        // $0.13 SEC plus $0.0119 TAF, rounded up to $0.02
+        assert_eq!(regulatory_fees(&Side::Sell, Quantity::from(100), dollars("10000")), dollars("0.15"));
+        // TAF tops out at $5.95
+        assert_eq!(
+            regulatory_fees(&Side::Sell, Quantity::from(100_000), dollars("100000")),
+            dollars("7.25")
+        );
+    }
+}
+
+/// Day trades found in a set of orders, as `(instrument url, trading day)` pairs
+///
+/// A day trade is a sell of something bought earlier the same trading day. Buying again after
+/// that and selling again is another day trade.
+pub fn day_trades(orders: &[Order]) -> Vec<(String, NaiveDate)> {
+    _walk_day_trades(orders).0
+}
+
+/// Walks filled orders oldest first. Also returns what's been bought but not yet sold on each
+/// day, since selling it would make another day trade.
+fn _walk_day_trades(orders: &[Order]) -> (Vec<(String, NaiveDate)>, HashSet<(String, NaiveDate)>) {
+    let mut filled: Vec<&Order> = orders
+        .iter()
+        .filter(|order| !order.data.cumulative_quantity.is_zero())
+        .collect();
+    filled.sort_by_key(|order| order.data.last_transaction_at);
+
+    let mut trades = Vec::new();
+    let mut open = HashSet::new();
+    for order in filled {
+        let key = (
+            order.data.instrument.to_owned(),
+            market_date(order.data.last_transaction_at),
+        );
+        match order.data.side {
+            Side::Buy => {
+                open.insert(key);
+            }
+            Side::Sell => {
+                if open.remove(&key) {
+                    trades.push(key);
+                }
+            }
+            Side::Unknown(_) => {}
+        }
+    }
+    (trades, open)
+}
+
+/// The trading day `days` business days before `date`, skipping weekends
+pub fn business_days_before(date: NaiveDate, days: u32) -> NaiveDate {
+    let mut date = date;
+    let mut left = days;
+    while left > 0 {
+        date = date.pred();
+        match date.weekday() {
+            Weekday::Sat | Weekday::Sun => {}
+            _ => left -= 1,
+        }
+    }
+    date
+}
+
+/// Returned instead of sending an order that would be the fourth day trade in five business
+/// days, which flags a margin account as a pattern day trader
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayTradeWarning {
+    /// Day trades already made in the window
+    pub day_trades: usize,
+}
+
+impl fmt::Display for DayTradeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "order would be day trade #{} in five business days; use allow_day_trade() to send it anyway",
+            self.day_trades + 1
+        )
+    }
+}
+
+impl StdError for DayTradeWarning {
+    fn description(&self) -> &str {
+        "order would flag the account as a pattern day trader"
+    }
+}
+
+#[cfg(test)]
+mod test_day_trades {
+    use super::test_fixtures::{account, instrument, order};
+    use super::{business_days_before, day_trades, _walk_day_trades, HTTPClient, OrderBuilder, Quantity, Side};
+    use chrono::naive::NaiveDate;
+
+    #[test]
+    fn round_trips() {
+        let orders = vec![
+            order("1", "aapl", "buy", "filled", "10", "2018-06-04T14:00:00Z"),
+            order("2", "aapl", "sell", "filled", "10", "2018-06-04T15:00:00Z"),
+            order("3", "aapl", "buy", "filled", "10", "2018-06-04T16:00:00Z"),
+            order("4", "aapl", "sell", "partially_filled", "5", "2018-06-04T17:00:00Z"),
+            // Held overnight
+            order("5", "msft", "buy", "filled", "10", "2018-06-04T15:00:00Z"),
+            order("6", "msft", "sell", "filled", "10", "2018-06-05T15:00:00Z"),
+            // Never filled
+            order("7", "tsla", "buy", "cancelled", "0", "2018-06-05T14:00:00Z"),
+            order("8", "tsla", "sell", "filled", "10", "2018-06-05T15:00:00Z"),
+            order("9", "amd", "buy", "filled", "10", "2018-06-05T16:00:00Z"),
+        ];
+        let trades = day_trades(&orders);
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|&(_, date)| date == NaiveDate::from_ymd(2018, 6, 4)));
+
+        let (_, open) = _walk_day_trades(&orders);
+        assert!(open.contains(&(
+            "https://api.robinhood.com/instruments/amd/".to_string(),
+            NaiveDate::from_ymd(2018, 6, 5)
+        )));
+    }
+
+    #[test]
+    fn business_days() {
+        // Friday, four business days back from Thursday
+        assert_eq!(
+            business_days_before(NaiveDate::from_ymd(2018, 6, 7), 4),
+            NaiveDate::from_ymd(2018, 6, 1)
+        );
+    }
+
+    #[test]
+    fn allowed() {
+        // Allowing the day trade skips the order history lookup entirely
+        let mut order = OrderBuilder::new(
+            HTTPClient::new(),
+            Side::Sell,
+            Quantity::from(10),
+            instrument("AAPL"),
+            account("margin"),
+        );
+        order.override_day_trade_checks();
+        assert!(!order.allow_day_trade);
+        order.allow_day_trade();
+        assert!(order._check_day_trades().is_ok());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderBuilder {
+    client: HTTPClient,
+    time_in_force: TimeInForce,
+    stop_price: Option<Price>,
+    instrument: Instrument,
+    override_dtbp_checks: bool,
+    _type: OrderType,
+    price: Option<Price>,
+    extended_hours: bool,
+    account: Account,
+    side: Side,
+    override_day_trade_checks: bool,
+    allow_day_trade: bool,
+    quantity: Quantity,
+    dollar_amount: Option<Decimal>,
+    trailing_peg: Option<TrailingPeg>,
+    tick_rounding: TickRounding,
+    collar_percent: Decimal,
+    ref_id: String,
+    risk: RiskPolicy,
+    journal: Option<Journal>,
+}
+
+impl OrderBuilder {
+    pub fn new(
+        ref mut client: HTTPClient,
+        side: Side,
+        quantity: Quantity,
+        instrument: Instrument,
+        account: Account,
+    ) -> OrderBuilder {
+        OrderBuilder {
+            client: client.to_owned(),
+
+            _type: OrderType::Market,
+            side: side,
+            time_in_force: TimeInForce::GoodForDay,
 
             price: None,
             stop_price: None,
             quantity: quantity,
+            dollar_amount: None,
+            trailing_peg: None,
 
             instrument: instrument,
             account: account,
@@ -775,71 +3822,521 @@ impl OrderBuilder {
             extended_hours: false,
             override_dtbp_checks: false,
             override_day_trade_checks: false,
+            allow_day_trade: false,
+            tick_rounding: TickRounding::Reject,
+            collar_percent: Decimal::from(5u64),
+            ref_id: Uuid::new_v4().to_string(),
+            risk: RiskPolicy::default(),
+            journal: None,
         }
     }
 
-    pub fn send(&self) -> Order {
-        let mut params = HashMap::new();
-        params.insert("account", self.account.url());
-        params.insert("instrument", self.instrument.url());
-        params.insert("symbol", self.instrument.symbol());
-        params.insert("type", self._type.to_owned());
-        params.insert("time_in_force", self.time_in_force.to_owned());
-        params.insert("trigger", "immediate".to_owned());
-        params.insert("quantity", self.quantity.to_string());
-        params.insert("side", self.side.to_owned());
+    /// Moves a price onto the instrument's tick size, or refuses it, per `tick_rounding`
+    fn _round_price(&self, price: Option<Price>) -> Result<Option<Price>, Error> {
+        match price {
+            Some(price) => {
+                let tick_size = price.tick_size(self.instrument.min_tick_size());
+                Ok(Some(price.round_to_tick(tick_size, self.tick_rounding)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn _quote(&self) -> Result<Quote, Error> {
+        let url = self.instrument.quote();
+        let mut res = self.client.get(url.as_str()).send()?;
+        _check_response("quote", &url, &res)?;
+        Ok(res.json::<Quote>()?)
+    }
+
+    /// The collar is a percentage strictly between 0 and 100
+    fn _validate_collar(&self) -> Result<(), Error> {
+        if self.collar_percent <= Decimal::default() || self.collar_percent >= Decimal::from(100u64) {
+            return Err(InvalidOrder::new(&format!(
+                "collar must be between 0% and 100%, not {}%",
+                self.collar_percent
+            )).into());
+        }
+        Ok(())
+    }
+
+    /// Fractional quantities and dollar amounts are only allowed on eligible instruments,
+    /// as good-for-day market or limit orders without a stop
+    fn _validate_quantity(&self) -> Result<(), Error> {
+        if let Some(amount) = self.dollar_amount {
+            if amount < Decimal::from(1u64) {
+                return Err(InvalidOrder::new("dollar-based orders must be for at least $1").into());
+            }
+            if self._type != OrderType::Market {
+                return Err(InvalidOrder::new("dollar-based orders must be market orders").into());
+            }
+        } else if self.quantity.value() <= Decimal::default() {
+            return Err(InvalidOrder::new("quantity must be greater than zero").into());
+        }
+
+        let fractional = self.dollar_amount.is_some() || !self.quantity.value().fract().is_zero();
+        if !fractional {
+            return Ok(());
+        }
+        if self.instrument.fractional_tradability() != Some("tradable".to_string()) {
+            return Err(InvalidOrder::new(&format!(
+                "{} is not eligible for fractional trading",
+                self.instrument.symbol()
+            )).into());
+        }
+        if self.quantity.value().normalize().scale() > 6 {
+            return Err(InvalidOrder::new("fractional quantities are limited to 6 decimal places").into());
+        }
+        if self.time_in_force != TimeInForce::GoodForDay {
+            return Err(InvalidOrder::new("fractional orders must be good for day").into());
+        }
+        if self.stop_price.is_some() {
+            return Err(InvalidOrder::new("fractional orders cannot have a stop price").into());
+        }
+        Ok(())
+    }
 
+    /// Trailing stops are market orders with a positive trail and no stop price of their own
+    fn _validate_trailing_stop(&self) -> Result<(), Error> {
+        let peg = match self.trailing_peg {
+            Some(ref peg) => peg,
+            None => return Ok(()),
+        };
+        if self._type != OrderType::Market || self.price.is_some() {
+            return Err(InvalidOrder::new("trailing stops cannot have a limit price").into());
+        }
         if self.stop_price.is_some() {
-            params.insert("stop_price", self.stop_price.unwrap().to_string());
-            params.insert("trigger", "stop".to_owned());
+            return Err(InvalidOrder::new("trailing stops set their own stop price").into());
         }
-        if self._type == "market" && self.price.is_none() {
-            // self.price =
-            // TODO: Get price from quote endpoint
+        if self.dollar_amount.is_some() || !self.quantity.value().fract().is_zero() {
+            return Err(InvalidOrder::new("trailing stops must be for whole shares").into());
         }
-        if self.price.is_some() {
-            params.insert("price", self.price.unwrap().to_string());
+        let trail = match (&peg.percentage, &peg.price) {
+            (&Some(percentage), _) => {
+                if percentage >= Decimal::from(100u64) {
+                    return Err(InvalidOrder::new("trailing percentage must be under 100").into());
+                }
+                percentage
+            }
+            (_, &Some(ref price)) => price.amount,
+            _ => return Err(InvalidOrder::new("trailing stop has no trail").into()),
+        };
+        if trail <= Decimal::default() {
+            return Err(InvalidOrder::new("trail must be greater than zero").into());
         }
+        Ok(())
+    }
+
+    /// Extended-hours orders must be plain good-for-day limit orders, sent on a trading day
+    /// before the extended session closes
+    fn _validate_extended_hours(&self) -> Result<(), Error> {
+        if !self.extended_hours {
+            return Ok(());
+        }
+        if self._type != OrderType::Limit {
+            return Err(InvalidOrder::new("extended-hours orders must be limit orders").into());
+        }
+        if self.time_in_force != TimeInForce::GoodForDay {
+            return Err(InvalidOrder::new("extended-hours orders must be good for day").into());
+        }
+        if self.stop_price.is_some() || self.trailing_peg.is_some() {
+            return Err(InvalidOrder::new("extended-hours orders cannot be stop orders").into());
+        }
+
+        let now = Utc::now();
+        let url = format!(
+            "{}hours/{}/",
+            self.instrument.market(),
+            market_date(now).format("%Y-%m-%d")
+        );
+        let mut res = self.client.get(url.as_str()).send()?;
+        _check_response("market hours", &url, &res)?;
+        let hours = res.json::<MarketHours>()?;
+        match hours.session_at(now) {
+            Session::Closed if !hours.is_open => Err(InvalidOrder::new(&format!(
+                "the market is closed on {}",
+                hours.date
+            )).into()),
+            Session::Closed if hours.extended_closes_at.map_or(false, |closes| now >= closes) => {
+                Err(InvalidOrder::new("extended-hours trading has ended for the day").into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuses a sell that would be the fourth day trade in five business days on a margin
+    /// account, unless the day trade has been explicitly allowed
+    ///
+    /// Accounts with $25,000 or more in equity aren't subject to the rule and can skip this
+    /// with `allow_day_trade`.
+    fn _check_day_trades(&self) -> Result<(), Error> {
+        if self.allow_day_trade
+            || self.side != Side::Sell
+            || self.account.type_field() != "margin"
+        {
+            return Ok(());
+        }
+
+        let (day_trades, day_trade) = self._day_trades()?;
+        if day_trades >= 3 && day_trade {
+            return Err(DayTradeWarning {
+                day_trades: day_trades,
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// Day trades in the last five trading days, and whether this order would make another
+    fn _day_trades(&self) -> Result<(usize, bool), Error> {
+        let today = market_date(Utc::now());
+        let start = business_days_before(today, 4);
+        let since = DateTime::<Utc>::from_utc(start.and_hms(0, 0, 0), Utc);
+        let orders = QueriedOrders::new_with_client(
+            self.client.to_owned(),
+            OrdersQuery::new().updated_since(since),
+        ).collect::<Result<Vec<Order>, Error>>()?;
+
+        let (trades, open) = _walk_day_trades(&orders);
+        let day_trades = trades.iter().filter(|&&(_, date)| date >= start).count();
+        let day_trade = self.side == Side::Sell && open.contains(&(self.instrument.url(), today));
+        Ok((day_trades, day_trade))
+    }
+
+    /// Shares of the instrument the account holds now
+    fn _position(&self) -> Result<Quantity, Error> {
+        let instrument_url = self.instrument.url();
+        let positions = Positions::new_with_client(self.client.to_owned()).set_next(self.account.positions());
+        for position in positions {
+            let position = position?;
+            if position.instrument() == instrument_url {
+                return Ok(position.quantity());
+            }
+        }
+        Ok(Quantity::default())
+    }
+
+    /// Works out the cost, buying power, and position change of this order without sending it
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use robinhood::Client;
+    /// let rh = Client::new().login("username", "password").build().unwrap();
+    /// let msft = rh.instrument_by_symbol("MSFT").unwrap();
+    /// let mut order = rh.buy(30, msft);
+    /// let preview = order.limit(100).preview().unwrap();
+    /// if preview.has_buying_power() {
+    ///     order.send().unwrap();
+    /// }
+    /// ```
+    pub fn preview(&self) -> Result<OrderPreview, Error> {
+        let payload = self.payload()?;
+        let quote = self._quote()?;
+        let quote_price = quote.reference_price(&self.side);
+
+        let estimated_price = match self._type {
+            OrderType::Limit => payload.price.unwrap_or(quote_price),
+            _ => quote_price,
+        };
+        let estimated_amount = payload.quantity * estimated_price;
+        let required_buying_power = match self.side {
+            Side::Buy => payload.quantity * payload.price.unwrap_or(quote_price),
+            _ => Decimal::default(),
+        };
+
+        let account_url = self.account.url();
+        let mut res = self.client.get(account_url.as_str()).send()?;
+        _check_response("account", &account_url, &res)?;
+        let account = Account::new(res.json::<AccountData>()?);
+        let day_trade_buying_power = match account.type_field().as_str() {
+            "margin" => Some(account.margin_balances().day_trade_buying_power),
+            _ => None,
+        };
+
+        let position_before = self._position()?;
+        let position_after = match self.side {
+            Side::Buy => position_before + payload.quantity,
+            _ => position_before - payload.quantity,
+        };
+
+        let (day_trades, day_trade) = self._day_trades()?;
+        Ok(OrderPreview {
+            estimated_fees: regulatory_fees(&self.side, payload.quantity, estimated_amount),
+            payload: payload,
+            quote_price: quote_price,
+            estimated_amount: estimated_amount,
+            required_buying_power: required_buying_power,
+            buying_power: account.buying_power(),
+            day_trade_buying_power: day_trade_buying_power,
+            position_before: position_before,
+            position_after: position_after,
+            day_trade: day_trade,
+            day_trades: day_trades,
+        })
+    }
+
+    /// Where a trailing stop starts out: the trail away from the last trade
+    fn _initial_trailing_stop(&self, quote: &Quote, peg: &TrailingPeg) -> Result<Price, Error> {
+        let last = quote.last_trade_price.value();
+        let trail = match (&peg.percentage, &peg.price) {
+            (&Some(percentage), _) => last * percentage / Decimal::from(100u64),
+            (_, &Some(ref price)) => price.amount,
+            _ => return Err(InvalidOrder::new("trailing stop has no trail").into()),
+        };
+        // Sell stops sit below the market and buy stops above it; round toward the market
+        // so the stop never starts out further away than asked
+        let (stop, rounding) = match self.side {
+            Side::Buy => (Price(last + trail), TickRounding::Down),
+            _ => (Price(last - trail), TickRounding::Up),
+        };
+        if stop.value() <= Decimal::default() {
+            return Err(InvalidOrder::new("trail is larger than the last trade price").into());
+        }
+        let tick_size = stop.tick_size(self.instrument.min_tick_size());
+        Ok(stop.round_to_tick(tick_size, rounding)?)
+    }
+
+    /// Builds exactly what `send` will post, after validation and quote lookups
+    ///
+    /// Limit and stop prices are checked against the instrument's tick size, so an order the
+    /// exchange would reject never leaves the client. Market orders without a price are
+    /// collared off of the current quote, which also sizes dollar-based orders. Stop and
+    /// trailing stop orders are collared off of their (initial) stop price instead, since
+    /// that's where they'll trade once triggered.
+    pub fn payload(&self) -> Result<OrderPayload, Error> {
+        self._validate_quantity()?;
+        self._validate_collar()?;
+
+        self._validate_trailing_stop()?;
+        self._validate_extended_hours()?;
+
+        let mut price = self._round_price(self.price)?;
+        let mut stop_price = self._round_price(self.stop_price)?;
+        let mut quantity = self.quantity;
+
+        let needs_quote = self.trailing_peg.is_some()
+            || (self._type == OrderType::Market
+                && ((price.is_none() && stop_price.is_none()) || self.dollar_amount.is_some()));
+        let quote = if needs_quote { Some(self._quote()?) } else { None };
+        if let (&Some(ref quote), &Some(ref peg)) = (&quote, &self.trailing_peg) {
+            stop_price = Some(self._initial_trailing_stop(quote, peg)?);
+        }
+        if self._type == OrderType::Market && price.is_none() {
+            price = match (stop_price, &quote) {
+                (Some(stop_price), _) => Some(_collar(
+                    stop_price,
+                    &self.side,
+                    self.collar_percent,
+                    self.instrument.min_tick_size(),
+                )),
+                (None, &Some(ref quote)) => Some(quote.collared_price(
+                    &self.side,
+                    self.collar_percent,
+                    self.instrument.min_tick_size(),
+                )),
+                (None, &None) => None,
+            };
+        }
+        if let (Some(amount), &Some(ref quote)) = (self.dollar_amount, &quote) {
+            quantity = _shares_for_amount(amount, quote.reference_price(&self.side))?;
+        }
+
+        let mut payload = OrderPayload {
+            account: self.account.url(),
+            instrument: self.instrument.url(),
+            symbol: self.instrument.symbol(),
+            type_field: self._type.to_owned(),
+            time_in_force: self.time_in_force.to_owned(),
+            trigger: Trigger::Immediate,
+            side: self.side.to_owned(),
+            quantity: quantity,
+            price: price,
+            stop_price: None,
+            dollar_based_amount: self.dollar_amount.map(|amount| DollarAmount {
+                amount: amount,
+                currency_code: "USD".to_string(),
+            }),
+            trailing_peg: self.trailing_peg.to_owned(),
+            ref_id: Some(self.ref_id.to_owned()),
+            extended_hours: self.extended_hours,
+            override_day_trade_checks: self.override_day_trade_checks,
+            override_dtbp_checks: self.override_dtbp_checks,
+        };
+
+        if let Some(stop_price) = stop_price {
+            payload.stop_price = Some(stop_price);
+            payload.trigger = Trigger::Stop;
+        }
+
+        Ok(payload)
+    }
 
-        params.insert("override_day_trade_checks", "true".to_string());
+    /// Places the order
+    ///
+    /// Sells that would trip the pattern day trader rule are refused locally; see
+    /// `allow_day_trade`.
+    ///
+    /// With a journal, the order is recorded before it's sent and refused if that fails. The
+    /// outcome is recorded too, but once the order has gone out its result is returned either
+    /// way; see `Journal::take_errors`.
+    pub fn send(&self) -> Result<Order, Error> {
+        let payload = match self.payload() {
+            Ok(payload) => payload,
+            Err(err) => {
+                if let Some(ref journal) = self.journal {
+                    journal.refused(&self.ref_id, None, &err);
+                }
+                return Err(err);
+            }
+        };
+        let checked = self
+            ._check_day_trades()
+            .and_then(|_| self.risk.check(self, &payload));
+        if let Err(err) = checked {
+            if let Some(ref journal) = self.journal {
+                journal.refused(&self.ref_id, Some(&payload), &err);
+            }
+            return Err(err);
+        }
 
-        if self._type.eq("limit") && self.stop_price.is_none() {
-            // params.insert("extended_hours", "true".to_string());
+        if let Some(ref journal) = self.journal {
+            journal.sent(&payload)?;
+        }
+        let result = _post_order(&self.client, &payload);
+        if let Some(ref journal) = self.journal {
+            journal.placed(&payload, &result);
         }
+        result
+    }
+
+    /// Id sent along with the order so Robinhood (and we) can tell a resend from a new order.
+    /// Every builder gets its own; clones share it.
+    pub fn ref_id(&self) -> &str {
+        &self.ref_id
+    }
+
+    /// Sends the order with a ref_id worked out elsewhere, such as from state saved before
+    /// sending, so a resend after a crash can be recognized
+    pub fn set_ref_id(&mut self, ref_id: &str) -> &mut OrderBuilder {
+        self.ref_id = ref_id.to_owned();
+        self
+    }
 
-        let mut req = self.client.post("https://api.robinhood.com/orders/");
-        req.form(&params.to_owned());
-        let res = req.send().unwrap().json::<OrderData>().unwrap();
-        Order { data: res }
+    /// Turns this into a dollar-based order: the quantity is worked out from the quote
+    /// when the order is sent
+    pub fn dollar_amount(&mut self, amount: Decimal) -> &mut OrderBuilder {
+        self.dollar_amount = Some(amount);
+        self
     }
 
     pub fn gfd(&mut self) -> &mut OrderBuilder {
-        self.time_in_force = "gfd".to_string();
+        self.time_in_force = TimeInForce::GoodForDay;
         self
     }
     pub fn gtc(&mut self) -> &mut OrderBuilder {
-        self.time_in_force = "gtc".to_string();
+        self.time_in_force = TimeInForce::GoodTilCancelled;
+        self
+    }
+    pub fn ioc(&mut self) -> &mut OrderBuilder {
+        self.time_in_force = TimeInForce::ImmediateOrCancel;
+        self
+    }
+    pub fn fok(&mut self) -> &mut OrderBuilder {
+        self.time_in_force = TimeInForce::FillOrKill;
         self
     }
     pub fn opg(&mut self) -> &mut OrderBuilder {
-        self.time_in_force = "opg".to_string();
+        self.time_in_force = TimeInForce::Opening;
+        self
+    }
+
+    /// Turns this into a trailing stop that follows the market by `percent` percent, dropping
+    /// any limit price
+    pub fn trailing_stop_percent(&mut self, percent: Decimal) -> &mut OrderBuilder {
+        self.trailing_peg = Some(TrailingPeg {
+            type_field: TrailingPegType::Percentage,
+            percentage: Some(percent),
+            price: None,
+        });
+        self._type = OrderType::Market;
+        self.price = None;
+        self
+    }
+
+    /// Turns this into a trailing stop that follows the market by a fixed dollar amount,
+    /// dropping any limit price
+    pub fn trailing_stop_amount(&mut self, amount: Decimal) -> &mut OrderBuilder {
+        self.trailing_peg = Some(TrailingPeg {
+            type_field: TrailingPegType::Price,
+            percentage: None,
+            price: Some(DollarAmount {
+                amount: amount,
+                currency_code: "USD".to_string(),
+            }),
+        });
+        self._type = OrderType::Market;
+        self.price = None;
+        self
+    }
+
+    /// Sends the order even if it makes a day trade that would flag the account as a pattern
+    /// day trader. This only skips the check made here; see `override_day_trade_checks` for
+    /// Robinhood's.
+    pub fn allow_day_trade(&mut self) -> &mut OrderBuilder {
+        self.allow_day_trade = true;
+        self
+    }
+
+    /// Asks Robinhood to skip its pattern day trader check. The local check still applies
+    /// unless `allow_day_trade` is set too.
+    pub fn override_day_trade_checks(&mut self) -> &mut OrderBuilder {
+        self.override_day_trade_checks = true;
         self
     }
 
-    pub fn stop(&mut self, price: f64) -> &mut OrderBuilder {
-        self.stop_price = Some(price);
+    /// Sends the order even if it exceeds day trade buying power
+    pub fn override_dtbp_checks(&mut self) -> &mut OrderBuilder {
+        self.override_dtbp_checks = true;
         self
     }
 
-    pub fn limit(&mut self, price: f64) -> &mut OrderBuilder {
-        self.price = Some(price);
-        self._type = "limit".to_owned();
+    /// Lets a limit order fill during pre-market and after-hours sessions
+    pub fn extended_hours(&mut self, extended_hours: bool) -> &mut OrderBuilder {
+        self.extended_hours = extended_hours;
         self
     }
 
-    pub fn _price(&mut self, price: f64) -> &mut OrderBuilder {
+    pub fn stop<P: Into<Price>>(&mut self, price: P) -> &mut OrderBuilder {
+        self.stop_price = Some(price.into());
+        self
+    }
+
+    pub fn limit<P: Into<Price>>(&mut self, price: P) -> &mut OrderBuilder {
+        self.price = Some(price.into());
+        self._type = OrderType::Limit;
+        self
+    }
+
+    /// Chooses what happens to limit and stop prices that aren't on a valid tick. Orders
+    /// with such prices are rejected locally by default.
+    pub fn tick_rounding(&mut self, rounding: TickRounding) -> &mut OrderBuilder {
+        self.tick_rounding = rounding;
+        self
+    }
+
+    /// How far past the quote a market order may fill, in percent. Defaults to 5%.
+    ///
+    /// Buys are capped at the ask plus the collar, sells floored at the bid minus it. Stop
+    /// orders are collared off of their stop price instead. Anything outside of 0% to 100% is
+    /// rejected when the order is sent.
+    pub fn collar(&mut self, percent: Decimal) -> &mut OrderBuilder {
+        self.collar_percent = percent;
+        self
+    }
+
+    pub fn _price<P: Into<Price>>(&mut self, price: P) -> &mut OrderBuilder {
         // Set collar price on market order
-        self.price = Some(price);
+        self.price = Some(price.into());
         self
     }
     // pub fn oauth_client(&mut self, client_string: &str) -> &mut OrderBuilder {
@@ -984,6 +4481,75 @@ impl OrderBuilder {
 #[cfg(test)]
 mod test_order_builder {
     //use super::Order;
+    use super::test_fixtures::builder;
+    use super::{_shares_for_amount, Decimal, Price, Quantity, Side};
+    use std::str::FromStr;
+
+    #[test]
+    fn dollar_sizing() {
+        let dollars = |s: &str| Decimal::from_str(s).unwrap();
+        let price = |s: &str| Price::from_str(s).unwrap();
+        assert_eq!(
+            _shares_for_amount(dollars("25"), price("3")).unwrap(),
+            Quantity::from_str("8.333333").unwrap()
+        );
+        assert_eq!(_shares_for_amount(dollars("30"), price("10")).unwrap(), Quantity::from(3));
+        // A halted or never-traded symbol can come back with nothing but zeros
+        assert!(_shares_for_amount(dollars("25"), price("0")).is_err());
+    }
+
+    #[test]
+    fn quantity() {
+        assert!(builder(Side::Buy, "10")._validate_quantity().is_ok());
+        assert!(builder(Side::Buy, "0")._validate_quantity().is_err());
+        assert!(builder(Side::Buy, "0.5")._validate_quantity().is_ok());
+        assert!(builder(Side::Buy, "0.0000001")._validate_quantity().is_err());
+        assert!(builder(Side::Buy, "0.5").gtc()._validate_quantity().is_err());
+        assert!(builder(Side::Buy, "0.5").stop(100)._validate_quantity().is_err());
+
+        let mut order = builder(Side::Buy, "0");
+        order.dollar_amount(Decimal::from(25u64));
+        assert!(order._validate_quantity().is_ok());
+        order.dollar_amount(Decimal::new(5, 1));
+        assert!(order._validate_quantity().is_err());
+        order.dollar_amount(Decimal::from(25u64)).limit(100);
+        assert!(order._validate_quantity().is_err());
+    }
+
+    #[test]
+    fn collar() {
+        let mut order = builder(Side::Sell, "1");
+        assert!(order._validate_collar().is_ok());
+        for percent in &[0i64, -5, 100, 150] {
+            order.collar(Decimal::from(*percent));
+            assert!(order._validate_collar().is_err(), "{}", percent);
+        }
+        order.collar(Decimal::new(995, 1));
+        assert!(order._validate_collar().is_ok());
+    }
+
+    #[test]
+    fn trailing_stop_drops_limit() {
+        let mut order = builder(Side::Sell, "10");
+        order.limit(100).trailing_stop_percent(Decimal::from(5u64));
+        assert_eq!(order.price, None);
+        assert!(order._validate_trailing_stop().is_ok());
+
+        order.limit(100);
+        assert!(order._validate_trailing_stop().is_err());
+    }
+
+    #[test]
+    fn stop_collar() {
+        // A sell stop far below the bid is floored off of its stop, not the quote, so it
+        // can still fill once triggered. No quote is needed to build it.
+        let payload = builder(Side::Sell, "10").stop(50).gtc().payload().unwrap();
+        assert_eq!(payload.stop_price, Some(Price::from(50)));
+        assert_eq!(payload.price, Some(Price::from_str("47.50").unwrap()));
+
+        let payload = builder(Side::Buy, "10").stop(120).gtc().payload().unwrap();
+        assert_eq!(payload.price, Some(Price::from(126)));
+    }
 
     #[test]
     fn order_builder() {
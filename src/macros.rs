@@ -1,9 +1,20 @@
+/// `nested { field, ... }` after the fields names the ones holding models of their own, so
+/// `ensure_known_fields` looks inside them too.
 #[macro_export]
 macro_rules! iter_builder {
     ($list_name:ident => $item_name:ident as $data_name:ident, $url:tt {
         $(  $(#[$meta:meta])*
             $attr_name:ident : $attr_type:ty = $attr_default:expr ),*
     })
+    => {
+        iter_builder!($list_name => $item_name as $data_name, $url {
+            $( $(#[$meta])* $attr_name : $attr_type = $attr_default ),*
+        } nested {});
+    };
+    ($list_name:ident => $item_name:ident as $data_name:ident, $url:tt {
+        $(  $(#[$meta:meta])*
+            $attr_name:ident : $attr_type:ty = $attr_default:expr ),*
+    } nested { $( $nested:ident ),* })
     => {
         /*#[derive(Debug, Clone)]
         pub struct $item_name {
@@ -23,12 +34,21 @@ macro_rules! iter_builder {
         }
 
 */
-// Could capture more fields here if needed
-#[serde(deny_unknown_fields)] // Debugging!
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct $data_name {
     $(  $(#[$meta])* // Can rename fields because rust is retarded
-        $attr_name : $attr_type ),*
+        $attr_name : $attr_type, )*
+    /// Fields the API sent that this version of the crate doesn't know about
+    #[serde(flatten)]
+    pub extra: ::serde_json::Map<String, ::serde_json::Value>,
+}
+
+impl KnownFields for $data_name {
+    fn ensure_known_fields(&self) -> Result<(), UnknownFields> {
+        _known_fields(stringify!($data_name), &self.extra)?;
+        $( self.$nested.ensure_known_fields()?; )*
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +62,11 @@ impl $item_name {
         $item_name { data: data }
     }
 
+    /// Fields the API sent that this version of the crate doesn't know about
+    pub fn extra(&self) -> &::serde_json::Map<String, ::serde_json::Value> {
+        &self.data.extra
+    }
+
     $(
     pub fn $attr_name(&self) -> $attr_type { // TODO: Renamed fields will need to be handled properly
         //$(#[$meta])*
@@ -55,6 +80,8 @@ pub struct $list_name {
     pub results: <Vec<$data_name> as IntoIterator>::IntoIter,
     pub next: Option<String>,
     pub client: HTTPClient,
+    /// Fail on items with unknown fields instead of collecting them in `extra`
+    pub strict: bool,
 }
 
 impl $list_name {
@@ -64,6 +91,7 @@ impl $list_name {
             results: vec![].into_iter(),
             next: Some($url.to_owned()),
             client: client.to_owned(),
+            strict: cfg!(feature = "strict"),
         }
     }
 
@@ -72,6 +100,12 @@ impl $list_name {
         self.to_owned()
     }
 
+    /// Turns unknown fields into errors. On by default with the `strict` feature.
+    pub fn set_strict(&mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self.to_owned()
+    }
+
     fn try_next(&mut self) -> Result<Option<$item_name>, Error> {
         // If the previous page has a Instrument that hasn't been looked at.
         if let Some(dep) = self.results.next() {
@@ -87,10 +121,16 @@ impl $list_name {
             .get(url.as_ref().map(String::as_str).unwrap())
             .send()?
             .json::<PaginatedApiResponse<$data_name>>()?;
+        if self.strict {
+            for data in &response.results {
+                data.ensure_known_fields()?;
+            }
+        }
         self.results = response.results.into_iter();
         self.next = response.next;
-        Ok(Some($item_name::new(self.results.next().unwrap())))
-        //Ok(self.results.next())
+        // An empty page (no search hits, for example) either ends iteration or
+        // points us at the next page.
+        self.try_next()
     }
 }
 
@@ -111,3 +151,67 @@ impl Iterator for $list_name {
 
 }
 }
+
+/// Builds an enum for one of the API's string-valued fields. Values this version of the crate
+/// doesn't recognize land in `Unknown` instead of failing to deserialize.
+#[macro_export]
+macro_rules! string_enum {
+    ($(#[$meta:meta])* $name:ident {
+        $(  $(#[$variant_meta:meta])*
+            $variant:ident => $value:tt ),*
+    })
+    => {
+$(#[$meta])*
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum $name {
+    $(  $(#[$variant_meta])*
+        $variant, )*
+    /// Sent by the API but not (yet) known to this crate
+    Unknown(String),
+}
+
+impl $name {
+    /// The value as the API spells it
+    pub fn as_str(&self) -> &str {
+        match *self {
+            $( $name::$variant => $value, )*
+            $name::Unknown(ref value) => value.as_str(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for $name {
+    fn from(value: &'a str) -> Self {
+        match value {
+            $( $value => $name::$variant, )*
+            _ => $name::Unknown(value.to_owned()),
+        }
+    }
+}
+
+impl ::std::fmt::Display for $name {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ::serde::Serialize for $name {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> ::serde::Deserialize<'de> for $name {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        let value = <String as ::serde::Deserialize>::deserialize(deserializer)?;
+        Ok($name::from(value.as_str()))
+    }
+}
+}
+}
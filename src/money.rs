@@ -0,0 +1,243 @@
+//! Exact decimal prices and share quantities
+//!
+//! Robinhood sends every amount as a string (`"12.3400"`). These are parsed straight into
+//! `Decimal`s so nothing is ever rounded through a float on the way in or out.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+use std::str::FromStr;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+
+macro_rules! decimal_newtype {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(pub Decimal);
+
+        impl $name {
+            pub fn new(value: Decimal) -> $name {
+                $name(value)
+            }
+
+            pub fn value(&self) -> Decimal {
+                self.0
+            }
+
+            pub fn is_zero(&self) -> bool {
+                self.0.is_zero()
+            }
+        }
+
+        impl From<Decimal> for $name {
+            fn from(value: Decimal) -> $name {
+                $name(value)
+            }
+        }
+
+        impl From<$name> for Decimal {
+            fn from(value: $name) -> Decimal {
+                value.0
+            }
+        }
+
+        impl From<u64> for $name {
+            fn from(value: u64) -> $name {
+                $name(Decimal::from(value))
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ::rust_decimal::Error;
+
+            fn from_str(s: &str) -> Result<$name, Self::Err> {
+                Ok($name(Decimal::from_str(s)?))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 - other.0)
+            }
+        }
+    };
+}
+
+decimal_newtype!(
+    /// A per-share price in dollars
+    Price
+);
+
+decimal_newtype!(
+    /// A number of shares
+    Quantity
+);
+
+/// What to do with a price that doesn't land on a valid tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TickRounding {
+    /// Refuse to send the order
+    Reject,
+    /// Round toward zero
+    Down,
+    /// Round away from zero
+    Up,
+    /// Round to the closest tick, halfway rounding up
+    Nearest,
+}
+
+impl Default for TickRounding {
+    fn default() -> TickRounding {
+        TickRounding::Reject
+    }
+}
+
+/// Returned when a price isn't a whole number of ticks and rounding is disabled
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidTick {
+    pub price: Price,
+    pub tick_size: Price,
+}
+
+impl fmt::Display for InvalidTick {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "price {} is not a multiple of the {} tick size",
+            self.price, self.tick_size
+        )
+    }
+}
+
+impl StdError for InvalidTick {
+    fn description(&self) -> &str {
+        "price is not a multiple of the tick size"
+    }
+}
+
+impl Price {
+    /// Smallest increment accepted at this price
+    ///
+    /// Prices of $1 and up trade in pennies, anything below in hundredths of a penny. An
+    /// instrument's own minimum tick wins when it's coarser than that.
+    pub fn tick_size(&self, min_tick_size: Option<Price>) -> Price {
+        let rule = if self.0 < Decimal::from(1u64) {
+            Decimal::new(1, 4)
+        } else {
+            Decimal::new(1, 2)
+        };
+        match min_tick_size {
+            Some(tick_size) if tick_size.0 > rule => tick_size,
+            _ => Price(rule),
+        }
+    }
+
+    /// Moves this price onto a whole number of ticks
+    pub fn round_to_tick(&self, tick_size: Price, rounding: TickRounding) -> Result<Price, InvalidTick> {
+        if tick_size.is_zero() {
+            return Ok(*self);
+        }
+        let ticks = self.0 / tick_size.0;
+        if ticks.fract().is_zero() {
+            return Ok(*self);
+        }
+        let ticks = match rounding {
+            TickRounding::Reject => {
+                return Err(InvalidTick {
+                    price: *self,
+                    tick_size: tick_size,
+                })
+            }
+            TickRounding::Down => ticks.floor(),
+            TickRounding::Up => ticks.ceil(),
+            TickRounding::Nearest => {
+                ticks.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+            }
+        };
+        Ok(Price(ticks * tick_size.0))
+    }
+}
+
+/// Shares times price is a dollar amount
+impl Mul<Price> for Quantity {
+    type Output = Decimal;
+
+    fn mul(self, price: Price) -> Decimal {
+        self.0 * price.0
+    }
+}
+
+impl Mul<Quantity> for Price {
+    type Output = Decimal;
+
+    fn mul(self, quantity: Quantity) -> Decimal {
+        self.0 * quantity.0
+    }
+}
+
+#[cfg(test)]
+mod test_money {
+    use super::{Price, Quantity, TickRounding};
+    use rust_decimal::Decimal;
+    use serde_json;
+    use std::str::FromStr;
+
+    #[test]
+    fn exact_arithmetic() {
+        let a = Price::from_str("0.1").unwrap();
+        let b = Price::from_str("0.2").unwrap();
+        assert_eq!(a + b, Price::from_str("0.3").unwrap());
+        assert_eq!(
+            Quantity::from(3) * Price::from_str("10.01").unwrap(),
+            Decimal::from_str("30.03").unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let price: Price = serde_json::from_str(r#""12.3400""#).unwrap();
+        assert_eq!(price, Price::from_str("12.34").unwrap());
+        assert_eq!(serde_json::to_string(&price).unwrap(), r#""12.3400""#);
+        assert_eq!(price.to_string(), "12.3400");
+    }
+
+    #[test]
+    fn tick_size() {
+        let price = |s: &str| Price::from_str(s).unwrap();
+        assert_eq!(price("12.34").tick_size(None), price("0.01"));
+        assert_eq!(price("0.5").tick_size(None), price("0.0001"));
+        assert_eq!(price("12.34").tick_size(Some(price("0.05"))), price("0.05"));
+        assert_eq!(price("0.5").tick_size(Some(price("0.0001"))), price("0.0001"));
+    }
+
+    #[test]
+    fn round_to_tick() {
+        let price = |s: &str| Price::from_str(s).unwrap();
+        let penny = price("0.01");
+
+        assert!(price("12.345").round_to_tick(penny, TickRounding::Reject).is_err());
+        assert_eq!(price("12.34").round_to_tick(penny, TickRounding::Reject).unwrap(), price("12.34"));
+        assert_eq!(price("12.345").round_to_tick(penny, TickRounding::Down).unwrap(), price("12.34"));
+        assert_eq!(price("12.341").round_to_tick(penny, TickRounding::Up).unwrap(), price("12.35"));
+        assert_eq!(price("12.345").round_to_tick(penny, TickRounding::Nearest).unwrap(), price("12.35"));
+        assert_eq!(price("12.344").round_to_tick(penny, TickRounding::Nearest).unwrap(), price("12.34"));
+    }
+}
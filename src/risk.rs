@@ -0,0 +1,426 @@
+//! Pre-trade risk checks
+//!
+//! A `RiskPolicy` set on the `ClientBuilder` is checked by every `OrderBuilder::send` and
+//! `Client::replace` from that client, before anything reaches Robinhood. Every limit is off
+//! until it's set.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::prelude::*;
+use failure::Error;
+use rust_decimal::Decimal;
+
+use super::{
+    market_time, OrderBuilder, OrderPayload, OrderType, Portfolio, PortfolioData, Price, Quantity,
+    Side, _check_response,
+};
+
+/// An order the `RiskPolicy` refused to send
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskViolation {
+    /// Shares times price is over the per-order limit
+    MaxNotional { notional: Decimal, limit: Decimal },
+    /// The order would leave more shares of `symbol` than allowed
+    MaxPosition {
+        symbol: String,
+        position: Quantity,
+        limit: Quantity,
+    },
+    /// The account is down at least `limit` today; only sells go out
+    MaxDailyLoss { loss: Decimal, limit: Decimal },
+    /// `limit` orders already went out in the last minute
+    MaxOrdersPerMinute { limit: usize },
+    SymbolNotAllowed { symbol: String },
+    /// Inside a no-trading window, in market (Eastern) time
+    NoTradingWindow { start: NaiveTime, end: NaiveTime },
+    /// The limit or stop price is more than `limit` percent away from the last trade
+    PriceDeviation {
+        price: Price,
+        last_trade_price: Price,
+        limit: Decimal,
+    },
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RiskViolation::MaxNotional { notional, limit } => {
+                write!(f, "order is worth {}, over the {} limit", notional, limit)
+            }
+            RiskViolation::MaxPosition {
+                ref symbol,
+                position,
+                limit,
+            } => write!(
+                f,
+                "order would leave {} shares of {}, over the {} limit",
+                position, symbol, limit
+            ),
+            RiskViolation::MaxDailyLoss { loss, limit } => write!(
+                f,
+                "down {} today, at or over the {} daily loss limit",
+                loss, limit
+            ),
+            RiskViolation::MaxOrdersPerMinute { limit } => {
+                write!(f, "already sent {} orders in the last minute", limit)
+            }
+            RiskViolation::SymbolNotAllowed { ref symbol } => {
+                write!(f, "{} is not on the allowed symbol list", symbol)
+            }
+            RiskViolation::NoTradingWindow { start, end } => {
+                write!(f, "trading is disabled between {} and {}", start, end)
+            }
+            RiskViolation::PriceDeviation {
+                price,
+                last_trade_price,
+                limit,
+            } => write!(
+                f,
+                "price {} is more than {}% away from the last trade at {}",
+                price, limit, last_trade_price
+            ),
+        }
+    }
+}
+
+impl StdError for RiskViolation {
+    fn description(&self) -> &str {
+        "order violates the risk policy"
+    }
+}
+
+/// Limits every order from a client must stay inside
+///
+/// Clones share the order rate counter, so every order builder from one client counts against
+/// the same limit.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// extern crate chrono;
+/// extern crate robinhood;
+///
+/// use chrono::NaiveTime;
+/// use robinhood::{Client, Decimal, RiskPolicy};
+/// let rh = Client::new()
+///     .login("username", "password")
+///     .risk_policy(
+///         RiskPolicy::new()
+///             .max_notional(Decimal::from(5000u64))
+///             .max_orders_per_minute(10)
+///             .allow_symbols(&["AAPL", "MSFT"])
+///             .no_trading_window(NaiveTime::from_hms(9, 30, 0), NaiveTime::from_hms(9, 45, 0)),
+///     )
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RiskPolicy {
+    max_notional: Option<Decimal>,
+    max_position: Option<Quantity>,
+    max_positions: HashMap<String, Quantity>,
+    max_daily_loss: Option<Decimal>,
+    max_orders_per_minute: Option<usize>,
+    allowed_symbols: Option<HashSet<String>>,
+    no_trading_windows: Vec<(NaiveTime, NaiveTime)>,
+    max_price_deviation: Option<Decimal>,
+    sent: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+impl RiskPolicy {
+    pub fn new() -> RiskPolicy {
+        RiskPolicy::default()
+    }
+
+    /// Largest dollar value of a single order: shares times the limit price, or the quote for
+    /// market orders
+    pub fn max_notional(&mut self, limit: Decimal) -> &mut RiskPolicy {
+        self.max_notional = Some(limit);
+        self
+    }
+
+    /// Most shares to hold of any one symbol
+    pub fn max_position<Q: Into<Quantity>>(&mut self, limit: Q) -> &mut RiskPolicy {
+        self.max_position = Some(limit.into());
+        self
+    }
+
+    /// Most shares to hold of `symbol`, instead of the `max_position` limit
+    pub fn max_position_for<Q: Into<Quantity>>(&mut self, symbol: &str, limit: Q) -> &mut RiskPolicy {
+        self.max_positions
+            .insert(symbol.to_uppercase(), limit.into());
+        self
+    }
+
+    /// Stops buying once the default portfolio is down this many dollars since the last close
+    pub fn max_daily_loss(&mut self, limit: Decimal) -> &mut RiskPolicy {
+        self.max_daily_loss = Some(limit);
+        self
+    }
+
+    pub fn max_orders_per_minute(&mut self, limit: usize) -> &mut RiskPolicy {
+        self.max_orders_per_minute = Some(limit);
+        self
+    }
+
+    /// Only trade these symbols. Calling it again adds to the list.
+    pub fn allow_symbols(&mut self, symbols: &[&str]) -> &mut RiskPolicy {
+        let allowed = self.allowed_symbols.get_or_insert_with(HashSet::new);
+        allowed.extend(symbols.iter().map(|symbol| symbol.to_uppercase()));
+        self
+    }
+
+    /// Sends nothing between `start` and `end` market (Eastern) time. A window may wrap past
+    /// midnight.
+    pub fn no_trading_window(&mut self, start: NaiveTime, end: NaiveTime) -> &mut RiskPolicy {
+        self.no_trading_windows.push((start, end));
+        self
+    }
+
+    /// Furthest a limit or stop price may be from the last trade, in percent
+    pub fn max_price_deviation(&mut self, percent: Decimal) -> &mut RiskPolicy {
+        self.max_price_deviation = Some(percent);
+        self
+    }
+
+    /// Checks an order about to be sent, counting it against the rate limit if it passes
+    pub fn check(&self, order: &OrderBuilder, payload: &OrderPayload) -> Result<(), Error> {
+        self.check_symbol(&payload.symbol)?;
+        self.check_time(Utc::now())?;
+
+        let needs_quote = self.max_price_deviation.is_some()
+            || (self.max_notional.is_some() && order._type != OrderType::Limit);
+        if needs_quote {
+            let quote = order._quote()?;
+            self.check_price(payload, quote.last_trade_price)?;
+            self.check_notional(payload, quote.reference_price(&payload.side))?;
+        } else if let Some(price) = payload.price {
+            self.check_notional(payload, price)?;
+        }
+
+        if self.max_position.is_some() || !self.max_positions.is_empty() {
+            self.check_position(&payload.symbol, &payload.side, order._position()?, payload.quantity)?;
+        }
+
+        if self.max_daily_loss.is_some() && payload.side != Side::Sell {
+            let url = order.account.portfolio_url();
+            let mut res = order.client.get(url.as_str()).send()?;
+            _check_response("portfolio", &url, &res)?;
+            let portfolio = Portfolio::new(res.json::<PortfolioData>()?);
+            self.check_daily_loss(portfolio.day_change())?;
+        }
+
+        self.check_rate(Instant::now())
+    }
+
+    fn check_symbol(&self, symbol: &str) -> Result<(), RiskViolation> {
+        match self.allowed_symbols {
+            Some(ref allowed) if !allowed.contains(&symbol.to_uppercase()) => {
+                Err(RiskViolation::SymbolNotAllowed {
+                    symbol: symbol.to_owned(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_time(&self, now: DateTime<Utc>) -> Result<(), RiskViolation> {
+        let time = market_time(now).time();
+        for &(start, end) in &self.no_trading_windows {
+            let inside = if start <= end {
+                time >= start && time < end
+            } else {
+                time >= start || time < end
+            };
+            if inside {
+                return Err(RiskViolation::NoTradingWindow {
+                    start: start,
+                    end: end,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_notional(&self, payload: &OrderPayload, price: Price) -> Result<(), RiskViolation> {
+        let price = match payload.type_field {
+            OrderType::Limit => payload.price.unwrap_or(price),
+            _ => price,
+        };
+        let notional = payload.quantity * price;
+        match self.max_notional {
+            Some(limit) if notional > limit => Err(RiskViolation::MaxNotional {
+                notional: notional,
+                limit: limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_price(&self, payload: &OrderPayload, last_trade_price: Price) -> Result<(), RiskViolation> {
+        let limit = match self.max_price_deviation {
+            Some(limit) if !last_trade_price.is_zero() => limit,
+            _ => return Ok(()),
+        };
+        // Market orders carry a collared price we picked ourselves
+        let limit_price = match payload.type_field {
+            OrderType::Limit => payload.price,
+            _ => None,
+        };
+        for &price in limit_price.iter().chain(payload.stop_price.iter()) {
+            let deviation = (price.value() - last_trade_price.value()).abs() / last_trade_price.value()
+                * Decimal::from(100u64);
+            if deviation > limit {
+                return Err(RiskViolation::PriceDeviation {
+                    price: price,
+                    last_trade_price: last_trade_price,
+                    limit: limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_position(
+        &self,
+        symbol: &str,
+        side: &Side,
+        held: Quantity,
+        quantity: Quantity,
+    ) -> Result<(), RiskViolation> {
+        let limit = match self.max_positions.get(&symbol.to_uppercase()) {
+            Some(&limit) => limit,
+            None => match self.max_position {
+                Some(limit) => limit,
+                None => return Ok(()),
+            },
+        };
+        let position = match *side {
+            Side::Buy => held + quantity,
+            _ => held - quantity,
+        };
+        if position > limit {
+            return Err(RiskViolation::MaxPosition {
+                symbol: symbol.to_owned(),
+                position: position,
+                limit: limit,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_daily_loss(&self, day_change: Decimal) -> Result<(), RiskViolation> {
+        match self.max_daily_loss {
+            Some(limit) if -day_change >= limit => Err(RiskViolation::MaxDailyLoss {
+                loss: -day_change,
+                limit: limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_rate(&self, now: Instant) -> Result<(), RiskViolation> {
+        let limit = match self.max_orders_per_minute {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let mut sent = self.sent.lock().unwrap();
+        while sent
+            .front()
+            .map_or(false, |&at| now.duration_since(at) >= Duration::from_secs(60))
+        {
+            sent.pop_front();
+        }
+        if sent.len() >= limit {
+            return Err(RiskViolation::MaxOrdersPerMinute { limit: limit });
+        }
+        sent.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_risk {
+    use super::{RiskPolicy, RiskViolation};
+    use chrono::prelude::*;
+    use rust_decimal::Decimal;
+    use std::time::Instant;
+    use {Price, Quantity, Side};
+
+    #[test]
+    fn symbols() {
+        let policy = RiskPolicy::new().allow_symbols(&["aapl"]).to_owned();
+        assert!(policy.check_symbol("AAPL").is_ok());
+        assert_eq!(
+            policy.check_symbol("MSFT"),
+            Err(RiskViolation::SymbolNotAllowed {
+                symbol: "MSFT".to_string(),
+            })
+        );
+        assert!(RiskPolicy::new().check_symbol("MSFT").is_ok());
+    }
+
+    #[test]
+    fn windows() {
+        let policy = RiskPolicy::new()
+            .no_trading_window(NaiveTime::from_hms(9, 30, 0), NaiveTime::from_hms(9, 45, 0))
+            .no_trading_window(NaiveTime::from_hms(23, 0, 0), NaiveTime::from_hms(1, 0, 0))
+            .to_owned();
+        // 13:35 UTC is 9:35 EDT in June; by 14:35 it's 10:35
+        assert!(policy.check_time(Utc.ymd(2018, 6, 4).and_hms(13, 35, 0)).is_err());
+        assert!(policy.check_time(Utc.ymd(2018, 6, 4).and_hms(13, 45, 0)).is_ok());
+        assert!(policy.check_time(Utc.ymd(2018, 6, 4).and_hms(14, 35, 0)).is_ok());
+        // In January 14:35 UTC is 9:35 EST
+        assert!(policy.check_time(Utc.ymd(2018, 1, 4).and_hms(14, 35, 0)).is_err());
+        // 11:30pm and 1:30am EDT
+        assert!(policy.check_time(Utc.ymd(2018, 6, 5).and_hms(3, 30, 0)).is_err());
+        assert!(policy.check_time(Utc.ymd(2018, 6, 5).and_hms(5, 30, 0)).is_ok());
+    }
+
+    #[test]
+    fn positions() {
+        let policy = RiskPolicy::new()
+            .max_position(100)
+            .max_position_for("AAPL", 10)
+            .to_owned();
+        assert!(policy.check_position("AAPL", &Side::Buy, Quantity::from(5), Quantity::from(5)).is_ok());
+        assert!(policy.check_position("AAPL", &Side::Buy, Quantity::from(5), Quantity::from(6)).is_err());
+        assert!(policy.check_position("AAPL", &Side::Sell, Quantity::from(20), Quantity::from(10)).is_ok());
+        assert!(policy.check_position("MSFT", &Side::Buy, Quantity::from(0), Quantity::from(50)).is_ok());
+    }
+
+    #[test]
+    fn daily_loss() {
+        let policy = RiskPolicy::new().max_daily_loss(Decimal::from(500u64)).to_owned();
+        assert!(policy.check_daily_loss(Decimal::from(-499i64)).is_ok());
+        assert!(policy.check_daily_loss(Decimal::from(-500i64)).is_err());
+    }
+
+    #[test]
+    fn rate() {
+        let policy = RiskPolicy::new().max_orders_per_minute(2).to_owned();
+        let shared = policy.clone();
+        let now = Instant::now();
+        assert!(policy.check_rate(now).is_ok());
+        assert!(shared.check_rate(now).is_ok());
+        assert_eq!(
+            policy.check_rate(now),
+            Err(RiskViolation::MaxOrdersPerMinute { limit: 2 })
+        );
+    }
+
+    #[test]
+    fn price_deviation() {
+        let price = |s: &str| s.parse::<Price>().unwrap();
+        let policy = RiskPolicy::new().max_price_deviation(Decimal::from(5u64)).to_owned();
+        let mut payload = ::test_fixtures::payload();
+        payload.price = Some(price("104"));
+        assert!(policy.check_price(&payload, price("100")).is_ok());
+        payload.price = Some(price("106"));
+        assert!(policy.check_price(&payload, price("100")).is_err());
+    }
+}
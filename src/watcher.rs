@@ -0,0 +1,313 @@
+//! Order update events
+//!
+//! `OrderWatcher` polls `/orders/` for anything updated since its last look and compares each
+//! order against the copy it saw before, turning the differences into `OrderEvent`s.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use failure::Error;
+use reqwest::Client as HTTPClient;
+
+use super::{Execution, Journal, Order, OrderData, OrderState, OrdersQuery, QueriedOrders};
+
+/// Something that happened to an order
+#[derive(Debug, Clone)]
+pub enum OrderEvent {
+    /// First time the watcher has seen the order
+    Placed(Order),
+    Confirmed(Order),
+    /// Some shares traded but the order is still working
+    PartialFill { order: Order, execution: Execution },
+    Filled(Order),
+    Cancelled(Order),
+    /// Rejected by Robinhood or failed outright
+    Rejected { order: Order, reason: Option<String> },
+}
+
+impl OrderEvent {
+    pub fn order(&self) -> &Order {
+        match *self {
+            OrderEvent::Placed(ref order)
+            | OrderEvent::Confirmed(ref order)
+            | OrderEvent::Filled(ref order)
+            | OrderEvent::Cancelled(ref order) => order,
+            OrderEvent::PartialFill { ref order, .. } | OrderEvent::Rejected { ref order, .. } => {
+                order
+            }
+        }
+    }
+}
+
+/// Works out what happened to an order between two sightings
+pub fn diff(previous: Option<&OrderData>, current: &Order) -> Vec<OrderEvent> {
+    let mut events = Vec::new();
+    if previous.is_none() {
+        events.push(OrderEvent::Placed(current.to_owned()));
+    }
+    let previous_state = previous.map(|data| data.state.to_owned());
+    let state_changed = previous_state.as_ref() != Some(&current.data.state);
+
+    let known: HashSet<&str> = previous
+        .map(|data| data.executions.iter().map(|e| e.id.as_str()).collect())
+        .unwrap_or_default();
+    let mut fills: Vec<&Execution> = current
+        .data
+        .executions
+        .iter()
+        .filter(|e| !known.contains(e.id.as_str()))
+        .collect();
+    // The execution that completes an order is reported as the fill itself
+    if current.data.state == OrderState::Filled && state_changed {
+        fills.pop();
+    }
+    for execution in fills {
+        events.push(OrderEvent::PartialFill {
+            order: current.to_owned(),
+            execution: execution.to_owned(),
+        });
+    }
+
+    if state_changed {
+        match current.data.state {
+            OrderState::Confirmed => events.push(OrderEvent::Confirmed(current.to_owned())),
+            OrderState::Filled => events.push(OrderEvent::Filled(current.to_owned())),
+            OrderState::Cancelled => events.push(OrderEvent::Cancelled(current.to_owned())),
+            OrderState::Rejected | OrderState::Failed => events.push(OrderEvent::Rejected {
+                order: current.to_owned(),
+                reason: current.data.reject_reason.to_owned(),
+            }),
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Polls for order changes and reports them as events
+///
+/// Use it as a blocking iterator, or hand it a callback with `subscribe`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use robinhood::Client;
+/// use robinhood::OrderEvent;
+/// let rh = Client::new().login("username", "password").build().unwrap();
+/// for event in rh.watch_orders() {
+///     match event.unwrap() {
+///         OrderEvent::Filled(order) => println!("Filled: {}", order.id()),
+///         event => println!("{:?}", event),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OrderWatcher {
+    client: HTTPClient,
+    since: DateTime<Utc>,
+    poll_interval: Duration,
+    seen: HashMap<String, OrderData>,
+    pending: VecDeque<OrderEvent>,
+    polled: bool,
+    journal: Option<Journal>,
+}
+
+impl OrderWatcher {
+    pub fn new_with_client(client: HTTPClient) -> OrderWatcher {
+        OrderWatcher {
+            client: client,
+            since: Utc::now(),
+            poll_interval: Duration::from_secs(5),
+            seen: HashMap::new(),
+            pending: VecDeque::new(),
+            polled: false,
+            journal: None,
+        }
+    }
+
+    /// Only report orders updated after this. Defaults to when the watcher was created.
+    pub fn since(&mut self, since: DateTime<Utc>) -> &mut OrderWatcher {
+        self.since = since;
+        self
+    }
+
+    /// How long to wait between polls. Defaults to five seconds.
+    pub fn poll_interval(&mut self, poll_interval: Duration) -> &mut OrderWatcher {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Records every change seen as an `Updated` journal entry
+    pub fn journal(&mut self, journal: &Journal) -> &mut OrderWatcher {
+        self.journal = Some(journal.to_owned());
+        self
+    }
+
+    /// Checks for changes once, returning whatever happened since the last check
+    pub fn poll(&mut self) -> Result<Vec<OrderEvent>, Error> {
+        let mut orders = QueriedOrders::new_with_client(
+            self.client.to_owned(),
+            OrdersQuery::new().updated_since(self.since),
+        ).collect::<Result<Vec<Order>, Error>>()?;
+        // Oldest changes first
+        orders.reverse();
+
+        let mut events = Vec::new();
+        let mut latest = self.since;
+        for order in orders {
+            if order.data.updated_at > latest {
+                latest = order.data.updated_at;
+            }
+            let changes = diff(self.seen.get(&order.data.id), &order);
+            if let Some(ref journal) = self.journal {
+                if !changes.is_empty() {
+                    journal.updated(&order);
+                }
+            }
+            events.extend(changes);
+            self.seen.insert(order.data.id.to_owned(), order.data);
+        }
+
+        // Finished orders only come back if they change again, which they won't
+        self.since = latest;
+        let since = self.since;
+        self.seen
+            .retain(|_, data| !data.state.is_terminal() || data.updated_at >= since);
+
+        Ok(events)
+    }
+
+    fn try_next(&mut self) -> Result<OrderEvent, Error> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+            if self.polled {
+                thread::sleep(self.poll_interval);
+            }
+            self.polled = true;
+            let events = self.poll()?;
+            self.pending.extend(events);
+        }
+    }
+
+    /// Watches on a background thread, handing every event to `callback`
+    ///
+    /// The watcher stops on the first error, which `Subscription::stop` hands back.
+    pub fn subscribe<F>(self, mut callback: F) -> Subscription
+    where
+        F: FnMut(OrderEvent) + Send + 'static,
+    {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let flag = stopped.clone();
+        let mut watcher = self;
+        let handle = thread::spawn(move || -> Result<(), Error> {
+            let tick = Duration::from_millis(100);
+            while !flag.load(Ordering::SeqCst) {
+                for event in watcher.poll()? {
+                    callback(event);
+                }
+                // Sleep in small steps so stopping doesn't wait out a long poll interval
+                let mut slept = Duration::from_secs(0);
+                while slept < watcher.poll_interval && !flag.load(Ordering::SeqCst) {
+                    thread::sleep(tick);
+                    slept += tick;
+                }
+            }
+            Ok(())
+        });
+        Subscription {
+            stopped: stopped,
+            handle: handle,
+        }
+    }
+}
+
+impl Iterator for OrderWatcher {
+    type Item = Result<OrderEvent, Error>;
+
+    /// Blocks until something happens. Never returns `None`.
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.try_next())
+    }
+}
+
+/// A watcher running on a background thread
+#[derive(Debug)]
+pub struct Subscription {
+    stopped: Arc<AtomicBool>,
+    handle: thread::JoinHandle<Result<(), Error>>,
+}
+
+impl Subscription {
+    /// Stops watching, waiting out any poll in progress, and returns the error that stopped
+    /// the watcher early, if any
+    pub fn stop(self) -> Result<(), Error> {
+        self.stopped.store(true, Ordering::SeqCst);
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(_) => Err(::failure::err_msg("order watcher panicked")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_watcher {
+    use super::{diff, OrderEvent};
+    use test_fixtures::{execution, order};
+
+    #[test]
+    fn placed_and_confirmed() {
+        let confirmed = order("1", "aapl", "buy", "confirmed", "0", "2018-06-04T14:00:00Z");
+        let events = diff(None, &confirmed);
+        assert_eq!(events.len(), 2);
+        match (&events[0], &events[1]) {
+            (&OrderEvent::Placed(_), &OrderEvent::Confirmed(_)) => {}
+            _ => panic!("{:?}", events),
+        }
+        assert!(diff(Some(&confirmed.data), &confirmed).is_empty());
+    }
+
+    #[test]
+    fn fills() {
+        let confirmed = order("1", "aapl", "buy", "confirmed", "0", "2018-06-04T14:00:00Z");
+
+        let mut partial = order("1", "aapl", "buy", "partially_filled", "4", "2018-06-04T14:00:01Z");
+        partial.data.executions.push(execution("e1", "4", "10"));
+        let events = diff(Some(&confirmed.data), &partial);
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            OrderEvent::PartialFill { ref execution, .. } => assert_eq!(execution.id, "e1"),
+            _ => panic!("{:?}", events),
+        }
+
+        let mut filled = order("1", "aapl", "buy", "filled", "10", "2018-06-04T14:00:02Z");
+        filled.data.executions.push(execution("e1", "4", "10"));
+        filled.data.executions.push(execution("e2", "3", "10"));
+        filled.data.executions.push(execution("e3", "3", "10"));
+        let events = diff(Some(&partial.data), &filled);
+        assert_eq!(events.len(), 2);
+        match (&events[0], &events[1]) {
+            (&OrderEvent::PartialFill { ref execution, .. }, &OrderEvent::Filled(_)) => {
+                assert_eq!(execution.id, "e2")
+            }
+            _ => panic!("{:?}", events),
+        }
+    }
+
+    #[test]
+    fn rejected() {
+        let mut rejected = order("1", "aapl", "sell", "rejected", "0", "2018-06-04T14:00:00Z");
+        rejected.data.reject_reason = Some("insufficient shares".to_string());
+        let events = diff(None, &rejected);
+        match events[1] {
+            OrderEvent::Rejected { ref reason, .. } => {
+                assert_eq!(reason.as_ref().unwrap(), "insufficient shares")
+            }
+            _ => panic!("{:?}", events),
+        }
+    }
+}